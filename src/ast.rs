@@ -1,35 +1,170 @@
 use crate::check::Ty;
-use crate::eval::{Error, Value};
+use crate::eval::{Error, Exn, Value};
 
 use parsel::{
     ast::{
-        Any, Brace, Ident, LeftAssoc, LitBool, LitInt, LitStr, Many, Maybe, Paren, Punctuated,
-        RightAssoc, Token,
+        Any, Brace, Bracket, Ident, LeftAssoc, LitBool, LitInt, LitStr, Maybe, Paren, Punctuated,
+        RightAssoc, Separated, Token,
     },
-    FromStr, Parse, ToTokens,
+    syn::parse::ParseStream,
+    FromStr, LineColumn, Parse, ToTokens, TokenStream,
 };
 
 mod kw {
-    parsel::custom_keyword!(pass);
-    parsel::custom_keyword!(print);
-    parsel::custom_keyword!(input);
-    parsel::custom_keyword!(int);
-    parsel::custom_keyword!(def);
-    parsel::custom_keyword!(str);
-    parsel::custom_keyword!(not);
-    parsel::custom_keyword!(and);
-    parsel::custom_keyword!(or);
-    parsel::custom_keyword!(None);
-    parsel::custom_keyword!(bool);
+    macro_rules! keywords {
+        ($($kw:ident),* $(,)?) => {
+            $(parsel::custom_keyword!($kw);)*
+
+            /// Every custom keyword, in the order they are declared.
+            pub const ALL: &[&::core::primitive::str] = &[$(stringify!($kw)),*];
+        };
+    }
+
+    keywords!(
+        pass, print, input, int, def, str, not, and, or, None, bool, list, import, from, raise,
+        except, finally, assert, lambda, nonlocal, global, class, case, is, Optional,
+    );
+}
+
+/// The keywords which Rust's lexer already knows about.
+const RUST_KEYWORDS: &[&str] = &[
+    "if", "else", "while", "for", "in", "return", "true", "false", "try", "as", "self", "enum",
+    "match",
+];
+
+/// Every keyword in the language.
+pub fn keywords() -> impl Iterator<Item = &'static str> {
+    kw::ALL.iter().chain(RUST_KEYWORDS).copied()
 }
 
 /// <prgm> ::= <blck>
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub struct Prgm {
+    pub imports: Any<Import>,
+    pub enums: Any<Enum>,
+    pub classes: Any<Class>,
     pub defns: Any<Defn>,
     pub main: Blck,
 }
 
+/// A use of the functions defined in another file, `utils.dwislpy` for the module `utils`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub enum Import {
+    /// `from utils import helper;`, which lets the program call `helper` directly.
+    From {
+        from: kw::from,
+        module: Ident,
+        import: kw::import,
+        names: Separated<Ident, Token!(,)>,
+        end: Token!(;),
+    },
+    /// `import utils;`, which lets the program call `utils.helper`.
+    Module {
+        import: kw::import,
+        module: Ident,
+        end: Token!(;),
+    },
+}
+
+impl Import {
+    /// The name of the imported module.
+    pub const fn module(&self) -> &Ident {
+        match self {
+            Self::From { module, .. } | Self::Module { module, .. } => module,
+        }
+    }
+}
+
+/// A type whose values are each one of its variants, like
+/// `enum Shape { Circle(int), Rect(int, int), Empty }`. Calling a variant with its fields, like
+/// `Rect(2, 3)`, makes one, and a variant without fields, like `Empty`, is one.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Enum {
+    pub enum_: Token!(enum),
+    pub name: Ident,
+    pub variants: Brace<Punctuated<Variant, Token!(,)>>,
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Variant {
+    pub name: Ident,
+    pub fields: Maybe<Paren<Punctuated<Type, Token!(,)>>>,
+}
+
+impl Variant {
+    /// The types of its fields.
+    pub fn fields(&self) -> impl Iterator<Item = &Type> {
+        self.fields
+            .as_prefix()
+            .into_iter()
+            .flat_map(|fields| fields.iter())
+    }
+}
+
+/// A type of object with named fields, like `class Point { x: int; y: int; }`. Calling it with
+/// a value for each field, like `Point(1, 2)`, makes one.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Class {
+    pub class: kw::class,
+    pub name: Ident,
+    pub body: Brace<ClassBody>,
+}
+
+/// The fields of a class, and then its methods.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct ClassBody {
+    pub fields: Any<Field>,
+    pub methods: Any<Method>,
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Field {
+    pub typed_ident: TypedIdent,
+    pub end: Token!(;),
+}
+
+impl Class {
+    pub fn fields(&self) -> impl Iterator<Item = &TypedIdent> {
+        self.body.fields.iter().map(|field| &field.typed_ident)
+    }
+}
+
+/// A function defined in a class, like `def norm(self) -> int { ... }`, which is called on an
+/// object of the class, like `p.norm()`, and gets the object as `self`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Method {
+    pub def: kw::def,
+    pub name: Ident,
+    pub params: Paren<MethodParams>,
+    pub ret: Maybe<ReturnType>,
+    pub rule: Nest,
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct MethodParams {
+    pub self_: Token!(self),
+    pub rest: Any<MethodParam>,
+}
+
+/// A parameter of a method after `self`, like the `, x: int` in `(self, x: int)`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct MethodParam {
+    pub comma: Token!(,),
+    pub param: TypedIdent,
+}
+
+impl Method {
+    /// The parameters after `self`.
+    pub fn params(&self) -> impl Iterator<Item = &TypedIdent> {
+        self.params.rest.iter().map(|param| &param.param)
+    }
+
+    /// The name that the object a method is called on has in its body.
+    pub fn self_ident(&self) -> Ident {
+        Ident::new("self", self.params.self_.span)
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub struct Defn {
     pub def: kw::def,
@@ -37,6 +172,14 @@ pub struct Defn {
     pub params: Paren<Punctuated<TypedIdent, Token!(,)>>,
     pub ret: Maybe<ReturnType>,
     pub rule: Nest,
+    pub captures: Captures,
+}
+
+impl Defn {
+    /// The docstring opening the body, if there is one.
+    pub fn doc(&self) -> Option<&str> {
+        self.rule.doc()
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
@@ -44,14 +187,27 @@ pub struct Nest {
     pub block: Brace<Blck>,
 }
 
+impl Nest {
+    /// The docstring opening this body of a function or method, if there is one.
+    pub fn doc(&self) -> Option<&str> {
+        match self.block.stmts.first() {
+            Some(Stmt::Doc(doc, _)) => Some(doc.as_ref()),
+            _ => None,
+        }
+    }
+}
+
 /// <blck> ::= <stmt> EOLN <stmt> OLN
+///
+/// A block may be empty, like the main program of a module that only defines functions.
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub struct Blck {
-    pub stmts: Many<Stmt>,
+    pub stmts: Any<Stmt>,
 }
 
 // <stmt> ::= <name> = <expn>
 //          | pass
+//          | <strg>
 //          | print ( <expn> )
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub enum Stmt {
@@ -73,8 +229,19 @@ pub enum Stmt {
         expn: Expn,
         end: Token!(;),
     },
+    /// Assign to a field of an object, like `p.x = 3;`.
+    SetField {
+        target: Indx,
+        equals: Token!(=),
+        expn: Expn,
+        end: Token!(;),
+    },
     Pass(kw::pass, Token!(;)),
-    Print(kw::print, Paren<Punctuated<Expn, Token!(,)>>, Token!(;)),
+    /// A bare string, which documents the function whose body it opens.
+    Doc(LitStr, Token!(;)),
+    Print(kw::print, Paren<Punctuated<PrintArg, Token!(,)>>, Token!(;)),
+    /// A function defined inside a block, which only the rest of the block can call.
+    Defn(#[parsel(recursive)] Box<Defn>),
     If {
         if_: Token!(if),
         cond: Expn,
@@ -85,12 +252,65 @@ pub enum Stmt {
         #[parsel(recursive)]
         else_nest: Box<Nest>,
     },
+    /// Run the nest of the first case whose pattern matches the value.
+    Match {
+        match_: Token!(match),
+        expn: Expn,
+        #[parsel(recursive)]
+        cases: Brace<Any<Case>>,
+    },
     While {
         while_: Token!(while),
         cond: Expn,
         #[parsel(recursive)]
         nest: Box<Nest>,
     },
+    /// Run the nest once for each item of a list, or character of a str.
+    For {
+        for_: Token!(for),
+        var: Ident,
+        in_: Token!(in),
+        iter: Expn,
+        #[parsel(recursive)]
+        nest: Box<Nest>,
+    },
+    /// Fail unless the condition holds, like `assert 0 < n, "n must be positive";`.
+    Assert {
+        assert: kw::assert,
+        cond: Expn,
+        msg: Maybe<Token!(,), Box<Expn>>,
+        end: Token!(;),
+    },
+    /// Raise an exception of a builtin class, like `raise ValueError("negative");`.
+    Raise {
+        raise: kw::raise,
+        class: Ident,
+        msg: Paren<Expn>,
+        end: Token!(;),
+    },
+    /// Run the nest, then the first handler that catches what it raises, if anything, and
+    /// then the `finally` nest whatever happened.
+    Try {
+        try_: Token!(try),
+        #[parsel(recursive)]
+        nest: Box<Nest>,
+        #[parsel(recursive)]
+        handlers: Any<Handler>,
+        #[parsel(recursive)]
+        finally: Maybe<kw::finally, Box<Nest>>,
+    },
+    /// Let a function assign to variables of the functions it is nested in.
+    Nonlocal {
+        nonlocal: kw::nonlocal,
+        names: Separated<Ident, Token!(,)>,
+        end: Token!(;),
+    },
+    /// Let a function assign to global variables.
+    Global {
+        global: kw::global,
+        names: Separated<Ident, Token!(,)>,
+        end: Token!(;),
+    },
     ReturnExpn {
         return_: Token!(return),
         expn: Expn,
@@ -100,11 +320,88 @@ pub enum Stmt {
         return_: Token!(return),
         end: Token!(;),
     },
-    FuncCall {
+    /// A call whose value, if it has one, is discarded, like `greet("world");` or
+    /// `self.bump();`.
+    Call(Indx, Token!(;)),
+}
+
+/// A case of a `match`, like `case Rect(w, h) { ... }`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Case {
+    pub case: kw::case,
+    pub pattern: Pattern,
+    #[parsel(recursive)]
+    pub nest: Box<Nest>,
+}
+
+/// What a case of a `match` matches.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub enum Pattern {
+    /// `_`, which matches anything.
+    Wildcard(Token!(_)),
+    Nmbr(LitInt),
+    Strg(LitStr),
+    Bool(LitBool),
+    /// A variant whose fields match the patterns, like `Rect(w, 3)`.
+    Variant {
         name: Ident,
-        args: Paren<Punctuated<Expn, Token!(,)>>,
-        end: Token!(;),
+        #[parsel(recursive)]
+        fields: Paren<Punctuated<Box<Pattern>, Token!(,)>>,
+    },
+    /// A variant without fields, or else a name for the value, which matches anything.
+    Name(Ident, IsVariant),
+}
+
+/// Whether a name in a pattern is a variant, rather than a variable for what it matches. It
+/// isn't written in the source, but filled in by the checker.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct IsVariant(pub bool);
+
+impl Parse for IsVariant {
+    fn parse(_: ParseStream<'_>) -> parsel::Result<Self> {
+        Ok(Self::default())
+    }
+}
+
+impl ToTokens for IsVariant {
+    fn to_tokens(&self, _: &mut TokenStream) {}
+}
+
+/// The module a function comes from, like the `utils.` in `utils.helper(x)`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Qual {
+    pub module: Ident,
+    pub dot: Token!(.),
+}
+
+/// An `except` clause, catching exceptions of a class, or any at all.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Handler {
+    pub except: kw::except,
+    /// The class it catches, and the name to give the exception's message, like
+    /// `ValueError as e`.
+    pub class: Maybe<Ident, Maybe<Token!(as), Ident>>,
+    #[parsel(recursive)]
+    pub nest: Box<Nest>,
+}
+
+impl Handler {
+    /// The name the exception's message is given in the handler, if any.
+    pub fn name(&self) -> Option<&Ident> {
+        self.class.as_ref().and_then(Maybe::as_ref)
+    }
+}
+
+/// Something to print, or an option like `sep=", "`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub enum PrintArg {
+    // a keyword must come first, since its name would parse as an expression
+    Kw {
+        name: Ident,
+        equals: Token!(=),
+        value: Expn,
     },
+    Pos(Expn),
 }
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
@@ -115,15 +412,13 @@ pub enum Updt {
 
 // <expn> ::= <addn>
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
-pub struct Expn(
-    pub  LeftAssoc<
-        And,
-        LeftAssoc<
-            Or,
-            RightAssoc<Comp, LeftAssoc<Add, LeftAssoc<Mult, LeftAssoc<Expt, UnExp<Not, Leaf>>>>>,
-        >,
-    >,
-);
+pub struct Expn(pub LeftAssoc<And, LeftAssoc<Or, Cmpn>>);
+
+// each level of precedence, from loosest to tightest
+pub type Cmpn = RightAssoc<Comp, Sum>;
+pub type Sum = LeftAssoc<Add, Prod>;
+pub type Prod = LeftAssoc<Mult, Powr>;
+pub type Powr = LeftAssoc<Expt, UnExp<Not, Indx>>;
 
 pub trait Binop {
     fn check(&self, lhs: Ty, rhs: Ty) -> Result<Ty, Error>;
@@ -148,9 +443,14 @@ pub struct Expt(Token!(^));
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub enum Comp {
-    Lt(Token!(<)),
+    // `<=` must come first, since `<` would match its first half
     Leq(Token!(<=)),
+    Lt(Token!(<)),
     Eq(Token!(==)),
+    // `is not` must come before `is`, for the same reason
+    IsNot(kw::is, kw::not),
+    /// Whether a value is None, like `x is None`.
+    Is(kw::is),
 }
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
@@ -163,11 +463,11 @@ impl Binop for Add {
     fn eval(&self, lhs: Value, rhs: Value) -> Result<Value, Error> {
         let left = lhs.expect_int()?;
         let right = rhs.expect_int()?;
-        Ok(match self {
-            Self::Plus(_) => left + right,
-            Self::Minus(_) => left - right,
-        }
-        .into())
+        let result = match self {
+            Self::Plus(_) => left.checked_add(right),
+            Self::Minus(_) => left.checked_sub(right),
+        };
+        Ok(result.ok_or(Error::overflow())?.into())
     }
 
     fn check(&self, lhs: Ty, rhs: Ty) -> Result<Ty, Error> {
@@ -181,22 +481,25 @@ impl Binop for Mult {
     fn eval(&self, lhs: Value, rhs: Value) -> Result<Value, Error> {
         let left = lhs.expect_int()?;
         let right = rhs.expect_int()?;
-        Ok(match self {
-            Self::Times(_) => left * right,
+        let result = match self {
+            Self::Times(_) => left.checked_mul(right),
             Self::Div(_) => {
                 if right == 0 {
-                    return Err("cannot divide by zero");
+                    return Err(Error::raise(
+                        Exn::ZeroDivisionError,
+                        "cannot divide by zero",
+                    ));
                 }
-                left / right
+                left.checked_div(right)
             }
             Self::Mod(_) => {
                 if right == 0 {
-                    return Err("cannot mod by zero");
+                    return Err(Error::raise(Exn::ZeroDivisionError, "cannot mod by zero"));
                 }
-                left % right
+                left.checked_rem(right)
             }
-        }
-        .into())
+        };
+        Ok(result.ok_or(Error::overflow())?.into())
     }
 
     fn check(&self, lhs: Ty, rhs: Ty) -> Result<Ty, Error> {
@@ -210,11 +513,19 @@ impl Binop for Expt {
     fn eval(&self, lhs: Value, rhs: Value) -> Result<Value, Error> {
         let left = lhs.expect_int()?;
         let right = rhs.expect_int()?;
-        if let Ok(exp) = right.try_into() {
-            Ok(left.pow(exp).into())
-        } else {
-            Err("negative powers are not supported since there are no floats in dwislpy")
-        }
+        let exp = match u32::try_from(right) {
+            Ok(exp) => exp,
+            Err(_) if right < 0 => {
+                return Err(Error::raise(
+                    Exn::ValueError,
+                    "negative powers are not supported since there are no floats in dwislpy",
+                ))
+            }
+            // only the powers of 0, 1 and -1 stay small, and they repeat every other exponent
+            Err(_) if (-1..=1).contains(&left) => 2 - u32::from(right % 2 == 1),
+            Err(_) => return Err(Error::raise(Exn::OverflowError, "exponent too large")),
+        };
+        Ok(left.checked_pow(exp).ok_or(Error::overflow())?.into())
     }
 
     fn check(&self, lhs: Ty, rhs: Ty) -> Result<Ty, Error> {
@@ -226,17 +537,39 @@ impl Binop for Expt {
 
 impl Binop for Comp {
     fn eval(&self, lhs: Value, rhs: Value) -> Result<Value, Error> {
+        // the checker makes sure one side is None when the other might be
+        let both_none = matches!((&lhs, &rhs), (Value::Unit, Value::Unit));
+        match self {
+            Self::Is(_) => return Ok(both_none.into()),
+            Self::IsNot(_, _) => return Ok((!both_none).into()),
+            Self::Eq(_) if matches!(lhs, Value::Unit) || matches!(rhs, Value::Unit) => {
+                return Ok(both_none.into());
+            }
+            _ => {}
+        }
         let left = lhs.expect_int()?;
         let right = rhs.expect_int()?;
         Ok(match self {
             Self::Lt(_) => left < right,
             Self::Leq(_) => left <= right,
             Self::Eq(_) => left == right,
+            Self::Is(_) | Self::IsNot(_, _) => unreachable!("compared with None above"),
         }
         .into())
     }
 
     fn check(&self, lhs: Ty, rhs: Ty) -> Result<Ty, Error> {
+        let none_test = matches!(self, Self::Is(_) | Self::IsNot(_, _))
+            || matches!(self, Self::Eq(_)) && (lhs == Ty::Unit || rhs == Ty::Unit);
+        if none_test {
+            // only something that might be None can be compared with it
+            let maybe_none = |ty: &Ty| matches!(ty, Ty::Unit | Ty::Optional(_));
+            return if lhs == Ty::Unit && maybe_none(&rhs) || rhs == Ty::Unit && maybe_none(&lhs) {
+                Ok(Ty::Bool)
+            } else {
+                Err("type error: can only compare a value that may be None with None".into())
+            };
+        }
         lhs.expect_int()?;
         rhs.expect_int()?;
         Ok(Ty::Bool)
@@ -304,31 +637,307 @@ impl Unop for Not {
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub enum Leaf {
     Inpt(kw::input, #[parsel(recursive)] Paren<Box<Expn>>),
-    Int(kw::int, #[parsel(recursive)] Paren<Box<Expn>>),
+    /// A conversion to int, with the base to parse a str in.
+    Int(
+        kw::int,
+        #[parsel(recursive)] Paren<Punctuated<Box<Expn>, Token!(,)>>,
+    ),
     Str(kw::str, #[parsel(recursive)] Paren<Box<Expn>>),
     FuncCall {
+        module: Maybe<Qual>,
         name: Ident,
         #[parsel(recursive)]
         args: Paren<Punctuated<Box<Expn>, Token!(,)>>,
     },
     Nmbr(LitInt),
     Strg(LitStr),
+    FStrg(#[parsel(recursive)] FStrg),
     Bool(LitBool),
-    Name(Ident),
+    // `None` and `lambda` must come before names, which they would otherwise parse as
     Unit(kw::None),
+    /// The object a method was called on.
+    SelfRef(Token!(self)),
+    Lambda(#[parsel(recursive)] Lambda),
+    Name(Ident),
+    List(#[parsel(recursive)] Bracket<Punctuated<Box<Expn>, Token!(,)>>),
     Expn(#[parsel(recursive)] Paren<Box<Expn>>),
 }
 
+/// An anonymous function, like `lambda x: int -> x + 1`, which returns the value of its body.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Lambda {
+    pub lambda: kw::lambda,
+    pub params: Maybe<Separated<TypedIdent, Token!(,)>>,
+    pub arrow: Token!(->),
+    #[parsel(recursive)]
+    pub body: Box<Expn>,
+    pub captures: Captures,
+}
+
+impl Lambda {
+    pub fn params(&self) -> impl Iterator<Item = &TypedIdent> {
+        self.params
+            .as_prefix()
+            .into_iter()
+            .flat_map(|params| params.iter())
+    }
+}
+
+/// The variables of enclosing functions that a function uses, which it shares with them. They
+/// aren't written in the source, but filled in by the checker.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Captures(pub Vec<Ident>);
+
+impl Parse for Captures {
+    fn parse(_: ParseStream<'_>) -> parsel::Result<Self> {
+        Ok(Self::default())
+    }
+}
+
+impl ToTokens for Captures {
+    fn to_tokens(&self, _: &mut TokenStream) {}
+}
+
+/// A formatted string literal, like `f"x = {x + 1:>5}"`.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct FStrg {
+    f: Ident,
+    /// The literal as written, since its escapes decide where the expressions in it are.
+    pub lit: parsel::syn::LitStr,
+    pub parts: Vec<FPart>,
+}
+
+/// A piece of an f-string.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum FPart {
+    Text(String),
+    Expn(Box<Expn>, Spec),
+}
+
+/// How to lay out a value in an f-string, from the part after the colon in `{x:*>5}`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Spec {
+    pub fill: char,
+    /// Where the value goes, if not the default for its type.
+    pub align: Option<Align>,
+    /// Whether to pad an int with zeros after its sign.
+    pub zero: bool,
+    pub width: usize,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+impl Parse for FStrg {
+    fn parse(input: ParseStream<'_>) -> parsel::Result<Self> {
+        let f: Ident = input.parse()?;
+        if f != "f" {
+            return Err(parsel::Error::new(f.span(), "expected an f-string"));
+        }
+        let lit: parsel::syn::LitStr = input.parse()?;
+        let parts = FPart::split(&lit).map_err(|msg| parsel::Error::new(lit.span(), msg))?;
+        Ok(Self { f, lit, parts })
+    }
+}
+
+impl ToTokens for FStrg {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.f.to_tokens(tokens);
+        self.lit.to_tokens(tokens);
+    }
+}
+
+impl FPart {
+    /// Split the literal of an f-string into its text and the expressions between braces.
+    fn split(lit: &parsel::syn::LitStr) -> Result<Vec<Self>, &'static str> {
+        let raw = lit.token().to_string();
+        let body = raw
+            .strip_prefix('"')
+            .and_then(|raw| raw.strip_suffix('"'))
+            .ok_or("an f-string cannot be raw")?;
+
+        // where each character of the body is in the source, just past the opening quote
+        let open = lit.span().start();
+        let mut at = LineColumn {
+            line: open.line,
+            column: open.column + 1,
+        };
+        let chars: Vec<(char, LineColumn)> = body
+            .chars()
+            .map(|c| {
+                let here = at;
+                if c == '\n' {
+                    at.line += 1;
+                    at.column = 0;
+                } else {
+                    at.column += 1;
+                }
+                (c, here)
+            })
+            .collect();
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut i = 0;
+        while let Some(&(c, _)) = chars.get(i) {
+            match (c, chars.get(i + 1).map(|&(c, _)| c)) {
+                ('\\', Some(next)) => {
+                    text.push(c);
+                    text.push(next);
+                    i += 2;
+                }
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    text.push(c);
+                    i += 2;
+                }
+                ('}', _) => return Err("single `}` is not allowed in an f-string"),
+                ('{', _) => {
+                    if !text.is_empty() {
+                        parts.push(Self::Text(unescape(&text)?));
+                        text.clear();
+                    }
+                    let (part, end) = Self::braced(&chars, i + 1)?;
+                    parts.push(part);
+                    i = end + 1;
+                }
+                _ => {
+                    text.push(c);
+                    i += 1;
+                }
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Self::Text(unescape(&text)?));
+        }
+        Ok(parts)
+    }
+
+    /// Parse the expression starting at the given character, up to its closing brace, which is
+    /// returned too.
+    fn braced(chars: &[(char, LineColumn)], start: usize) -> Result<(Self, usize), &'static str> {
+        let mut depth = 0;
+        let mut in_str = false;
+        let mut colon = None;
+        let mut end = None;
+        let mut i = start;
+        while let Some(&(c, _)) = chars.get(i) {
+            match c {
+                // a quote inside the literal has to be escaped
+                '\\' => {
+                    in_str ^= chars.get(i + 1).map(|&(c, _)| c) == Some('"');
+                    i += 1;
+                }
+                _ if in_str => (),
+                '(' | '[' | '{' => depth += 1,
+                '}' if depth == 0 => {
+                    end = Some(i);
+                    break;
+                }
+                ')' | ']' | '}' => depth -= 1,
+                ':' if depth == 0 && colon.is_none() => colon = Some(i),
+                _ => (),
+            }
+            i += 1;
+        }
+        let end = end.ok_or("unmatched `{` in an f-string")?;
+        let expn_end = colon.unwrap_or(end);
+        if chars[start..expn_end]
+            .iter()
+            .all(|(c, _)| c.is_whitespace())
+        {
+            return Err("empty expression in an f-string");
+        }
+
+        // pad the expression out to where it is in the source, so that its spans are right
+        let at = chars[start].1;
+        let mut source = "\n".repeat(at.line - 1) + &" ".repeat(at.column);
+        source.extend(chars[start..expn_end].iter().map(|&(c, _)| c));
+        let source = source.replace("\\\"", "\"");
+        let expn = source
+            .parse()
+            .map_err(|_| "invalid expression in an f-string")?;
+
+        let spec = match colon {
+            Some(colon) => chars[colon + 1..end].iter().map(|&(c, _)| c).collect(),
+            None => String::new(),
+        };
+        let spec = Spec::parse(&spec).ok_or("invalid format spec in an f-string")?;
+        Ok((Self::Expn(Box::new(expn), spec), end))
+    }
+}
+
+/// The value of some text from a string literal, with its escapes.
+fn unescape(text: &str) -> Result<String, &'static str> {
+    parsel::parse_str::<parsel::syn::LitStr>(&format!("\"{text}\""))
+        .map(|lit| lit.value())
+        .map_err(|_| "invalid escape in an f-string")
+}
+
+impl Spec {
+    /// Parse a spec like `*>5`, `^9` or `03`.
+    fn parse(spec: &str) -> Option<Self> {
+        let chars: Vec<char> = spec.chars().collect();
+        let align = |c| match c {
+            '<' => Some(Align::Left),
+            '>' => Some(Align::Right),
+            '^' => Some(Align::Center),
+            _ => None,
+        };
+        let (fill, align, rest) = match chars[..] {
+            [fill, c, ..] if align(c).is_some() => (fill, align(c), &chars[2..]),
+            [c, ..] if align(c).is_some() => (' ', align(c), &chars[1..]),
+            _ => (' ', None, &chars[..]),
+        };
+        let (zero, rest) = match rest {
+            ['0', rest @ ..] => (true, rest),
+            rest => (false, rest),
+        };
+        let width = if rest.is_empty() {
+            0
+        } else {
+            rest.iter().collect::<String>().parse().ok()?
+        };
+        Some(Self {
+            fill,
+            align,
+            zero,
+            width,
+        })
+    }
+}
+
+/// A leaf indexed, called, or with its fields taken any number of times, like `words[0][1]`,
+/// `make_adder(1)(2)` or `line.start.x`.
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub struct Indx {
+    pub leaf: Leaf,
+    #[parsel(recursive)]
+    pub postfixes: Any<Postfix>,
+}
+
+#[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
+pub enum Postfix {
+    Index(#[parsel(recursive)] Bracket<Box<Expn>>),
+    /// A call of the function to its left, which has no name of its own.
+    Call(#[parsel(recursive)] Paren<Punctuated<Box<Expn>, Token!(,)>>),
+    /// A field of the object to its left, like `p.x`.
+    Field(Token!(.), Ident),
+}
+
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub struct ReturnType {
-    arrow: Token!(->),
-    ty: Type,
+    pub arrow: Token!(->),
+    pub ty: Type,
 }
 
 #[derive(PartialEq, Eq, Debug, Parse, ToTokens, FromStr, Clone)]
 pub struct TypedIdent {
     pub ident: Ident,
-    colon: Token!(:),
+    pub colon: Token!(:),
     pub ty: Type,
 }
 
@@ -338,4 +947,17 @@ pub enum Type {
     Bool(kw::bool),
     Str(kw::str),
     Unit(kw::None),
+    List(kw::list, #[parsel(recursive)] Bracket<Box<Type>>),
+    /// A function, like `(int, str) -> bool`, or `() -> None` for one that returns nothing.
+    Func {
+        #[parsel(recursive)]
+        params: Paren<Punctuated<Box<Type>, Token!(,)>>,
+        arrow: Token!(->),
+        #[parsel(recursive)]
+        ret: Box<Type>,
+    },
+    /// A value of the other type, or None, like `Optional[int]`.
+    Optional(kw::Optional, #[parsel(recursive)] Bracket<Box<Type>>),
+    /// A class or an enum, by name.
+    Named(Ident),
 }
@@ -2,23 +2,66 @@ use std::collections::HashMap;
 
 use parsel::ast::{LeftAssoc, RightAssoc};
 use parsel::syn::Ident;
-use parsel::Spanned;
+use parsel::{Span, Spanned};
 
 use crate::ast::*;
-use crate::eval::Error;
+use crate::eval::{Error, Exn};
+use crate::pattern;
 
-trait Check {
+pub trait Check {
     type Info;
 
     fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error>;
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Ty {
     Int,
     Bool,
     Str,
     Unit,
+    List(Box<Ty>),
+    /// A function, taking arguments of the parameter types and returning the other, which is
+    /// `None` for one that returns nothing.
+    Func(Vec<Ty>, Box<Ty>),
+    /// An object of a class, or a value of an enum, by name.
+    Named(String),
+    /// A value of the other type, or None.
+    Optional(Box<Ty>),
+    /// Anything at all, where what a value is doesn't matter.
+    Any,
+    /// A list of anything, or a str, which only builtins like `len` can accept.
+    ListOrStr,
+    /// The items of an empty list, which has none to tell their type, so that it can go
+    /// wherever a list of any type is expected.
+    Never,
+}
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Bool => write!(f, "bool"),
+            Self::Str => write!(f, "str"),
+            Self::Unit => write!(f, "None"),
+            Self::List(item) => write!(f, "list[{item}]"),
+            Self::Func(params, ret) => {
+                write!(f, "(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{param}")?;
+                }
+                write!(f, ") -> {ret}")
+            }
+            Self::Named(name) => write!(f, "{name}"),
+            Self::Optional(ty) => write!(f, "Optional[{ty}]"),
+            Self::Any => write!(f, "any"),
+            Self::ListOrStr => write!(f, "list or str"),
+            Self::Never => write!(f, "never"),
+        }
+    }
 }
 
 impl From<&Type> for Ty {
@@ -28,16 +71,107 @@ impl From<&Type> for Ty {
             Type::Bool(_) => Self::Bool,
             Type::Str(_) => Self::Str,
             Type::Unit(_) => Self::Unit,
+            Type::List(_, item) => Self::List(Box::new((&***item).into())),
+            Type::Func { params, ret, .. } => Self::Func(
+                params.iter().map(|param| (&**param).into()).collect(),
+                Box::new((&**ret).into()),
+            ),
+            Type::Optional(_, ty) => Self::optional((&***ty).into()),
+            Type::Named(name) => Self::Named(name.to_string()),
         }
     }
 }
 
+impl From<&ArrowType> for Ty {
+    fn from(arrow: &ArrowType) -> Self {
+        let ret = arrow.return_type.clone().unwrap_or(Self::Unit);
+        Self::Func(arrow.params.clone(), Box::new(ret))
+    }
+}
+
 impl Ty {
+    /// Whether a value of the other type can go where this type is expected.
+    pub fn accepts(&self, other: &Ty) -> bool {
+        match (self, other) {
+            (Self::Any, _) | (_, Self::Never) => true,
+            (Self::ListOrStr, other) => matches!(other, Self::List(_) | Self::Str),
+            (Self::List(item), Self::List(other)) => item.accepts(other),
+            (Self::Optional(_), Self::Unit) => true,
+            (Self::Optional(ty), Self::Optional(other)) => ty.accepts(other),
+            (Self::Optional(ty), other) => ty.accepts(other),
+            // the function must take whatever it could be given, and give back what is expected
+            (Self::Func(params, ret), Self::Func(others, other)) => {
+                params.len() == others.len()
+                    && params
+                        .iter()
+                        .zip(others)
+                        .all(|(param, other)| other.accepts(param))
+                    && ret.accepts(other)
+            }
+            _ => self == other,
+        }
+    }
+
+    /// A value of this type, or None.
+    fn optional(self) -> Self {
+        match self {
+            Self::Unit | Self::Optional(_) => self,
+            ty => Self::Optional(Box::new(ty)),
+        }
+    }
+
+    /// The type of a value that is of either type, if they have one.
+    fn join(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            (ty, other) if ty == other => Some(ty),
+            (Self::Never, ty) | (ty, Self::Never) => Some(ty),
+            (Self::List(item), Self::List(other)) => Some(Self::List(Box::new(item.join(*other)?))),
+            (Self::Unit, ty) | (ty, Self::Unit) => Some(ty.optional()),
+            (Self::Optional(ty), other) | (other, Self::Optional(ty)) if *ty == other => {
+                Some(Self::Optional(ty))
+            }
+            _ => None,
+        }
+    }
+
+    /// Check that a value of the other type can be used where one of this type is needed,
+    /// failing with the message if not.
+    fn expect(&self, other: &Ty, msg: &'static str) -> Result<(), Error> {
+        if self.accepts(other) {
+            Ok(())
+        } else if matches!(other, Self::Optional(ty) if self.accepts(ty)) {
+            Err(format!("{msg}: it may be None").into())
+        } else {
+            Err(msg.into())
+        }
+    }
+
+    /// The error for a value of this type where a value of another is needed.
+    fn expected(&self, what: &str) -> Error {
+        if let Self::Optional(_) = self {
+            format!("type error: expected {what}, but it may be None").into()
+        } else {
+            format!("type error: expected {what}").into()
+        }
+    }
+
+    /// The signature of a function of this type.
+    fn arrow(self) -> Result<ArrowType, Error> {
+        let Self::Func(params, ret) = self else {
+            return Err(self.expected("function"));
+        };
+        Ok(ArrowType {
+            return_type: Some(*ret).filter(|ret| *ret != Self::Unit),
+            params,
+            defaults: 0,
+        })
+    }
+
     pub fn expect_str(self) -> Result<(), Error> {
         if let Self::Str = self {
             Ok(())
         } else {
-            Err("type error: expected str")
+            Err(self.expected("str"))
         }
     }
 
@@ -45,7 +179,7 @@ impl Ty {
         if let Self::Int = self {
             Ok(())
         } else {
-            Err("type error: expected int")
+            Err(self.expected("int"))
         }
     }
 
@@ -53,96 +187,922 @@ impl Ty {
         if let Self::Bool = self {
             Ok(())
         } else {
-            Err("type error: expected bool")
+            Err(self.expected("bool"))
         }
     }
 }
 
-#[derive(Clone, Copy)]
-enum Rtns {
+#[derive(Clone)]
+pub enum Rtns {
     Fallthrough,
     MightReturn(Ty),
     Returns(Ty),
+    /// Always raises an exception, so never gets to the end.
+    Diverges,
 }
 
 impl Rtns {
     /// Determine which type to return if one branch returns self and the other returns other
     fn reconcile(self, other: Rtns) -> Result<Rtns, Error> {
+        // one might return None where the other returns a value, which makes it optional
+        let join = |t: Ty, q: Ty| t.join(q).ok_or_else(|| Error::new("mismatched types"));
         Ok(match (self, other) {
+            (Self::Diverges, other) | (other, Self::Diverges) => other,
             (Self::Fallthrough, Self::Fallthrough) => Self::Fallthrough,
             (Self::Fallthrough, Self::MightReturn(t)) => Self::MightReturn(t),
             (Self::Fallthrough, Self::Returns(t)) => Self::MightReturn(t),
             (Self::MightReturn(t), Self::Fallthrough) => Self::MightReturn(t),
-            (Self::MightReturn(t), Self::MightReturn(q)) if t == q => Self::MightReturn(t),
-            (Self::MightReturn(t), Self::MightReturn(q)) => {
-                return Err("mismatched types");
-            }
-            (Self::MightReturn(t), Self::Returns(q)) if t == q => Self::MightReturn(t),
-            (Self::MightReturn(t), Self::Returns(q)) => {
-                return Err("mismatched types");
+            (Self::MightReturn(t), Self::MightReturn(q) | Self::Returns(q)) => {
+                Self::MightReturn(join(t, q)?)
             }
             (Self::Returns(t), Self::Fallthrough) => Self::MightReturn(t),
-            (Self::Returns(t), Self::MightReturn(q)) if t == q => Self::MightReturn(t),
-            (Self::Returns(t), Self::MightReturn(q)) => {
-                return Err("mismatched types");
+            (Self::Returns(t), Self::MightReturn(q)) => Self::MightReturn(join(t, q)?),
+            (Self::Returns(t), Self::Returns(q)) => Self::Returns(join(t, q)?),
+        })
+    }
+
+    /// Determine which type to return if a `finally` nest returning other runs after self,
+    /// however self ended
+    fn then_finally(self, other: Rtns) -> Result<Rtns, Error> {
+        Ok(match other {
+            Self::Fallthrough => self,
+            // the finally nest has the last word, but must agree with what self returns
+            Self::Returns(t) => {
+                self.reconcile(Self::Returns(t.clone()))?;
+                Self::Returns(t)
             }
-            (Self::Returns(t), Self::Returns(q)) if t == q => Self::Returns(t),
-            (Self::Returns(t), Self::Returns(q)) => return Err("mismatched types"),
+            Self::Diverges => Self::Diverges,
+            other => self.reconcile(other)?,
+        })
+    }
+
+    /// Determine which type to return if self runs first and then other runs after it
+    fn then(self, other: Rtns) -> Result<Rtns, Error> {
+        let join = |t: Ty, q: Ty| t.join(q).ok_or_else(|| Error::new("mismatched types"));
+        Ok(match (self, other) {
+            (Self::Fallthrough, other) => other,
+            (Self::MightReturn(t), Self::Diverges) => Self::Returns(t),
+            (Self::MightReturn(t), Self::Fallthrough) => Self::MightReturn(t),
+            (Self::MightReturn(t), Self::MightReturn(q)) => Self::MightReturn(join(t, q)?),
+            (Self::MightReturn(t), Self::Returns(q)) => Self::Returns(join(t, q)?),
+            // already returned, but we have more code
+            (Self::Returns(_), _) => return Err("unexpected statement; already returned".into()),
+            (Self::Diverges, _) => return Err("unexpected statement; already raised".into()),
         })
     }
 }
 
+/// What the checker knows about a class.
+#[derive(Clone, Default)]
+pub struct ClassType {
+    /// Its fields, with their types, in the order its constructor takes them.
+    pub fields: Vec<(Ident, Ty)>,
+    /// The signatures of its methods, leaving out `self`.
+    pub methods: HashMap<Ident, ArrowType>,
+}
+
+#[derive(Clone)]
 pub struct ArrowType {
     pub return_type: Option<Ty>,
     pub params: Vec<Ty>,
+    /// How many of the last parameters a call can leave out, which only builtins can do.
+    pub defaults: usize,
+}
+
+impl std::fmt::Display for ArrowType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            if i + self.defaults >= self.params.len() {
+                write!(f, "[{param}]")?;
+            } else {
+                write!(f, "{param}")?;
+            }
+        }
+        let return_type = self.return_type.as_ref().unwrap_or(&Ty::Unit);
+        write!(f, ") -> {return_type}")
+    }
+}
+
+/// A name in the source, as the checker resolved it.
+#[derive(Clone)]
+pub struct Ref {
+    /// The name where it is used.
+    pub name: Ident,
+    /// The name where it was declared.
+    pub decl: Ident,
+    /// What the name refers to, like `x: int`.
+    pub desc: String,
+    /// The body of the function the name is used in, or None in the main program.
+    pub scope: Option<Span>,
+}
+
+#[derive(Default, Clone)]
+pub struct DefTypes {
+    /// The global functions, from the host, the program, and what it imports by name.
+    table: HashMap<Ident, ArrowType>,
+    /// The functions defined in the blocks being checked, in this function or the main program.
+    locals: HashMap<Ident, ArrowType>,
+    /// Whether this is the scope of a function's body, rather than of the main program.
+    function: bool,
+    /// The functions defined in the functions this one is nested in, which it captures by
+    /// calling them.
+    enclosing: HashMap<Ident, ArrowType>,
+    /// The functions this function captures, in the order it first uses them.
+    captures: Vec<Ident>,
+    /// The functions of each module imported whole, for calls like `utils.helper()`.
+    modules: HashMap<Ident, HashMap<Ident, ArrowType>>,
+    /// The fields of each class, in the order its constructor takes them, including the classes
+    /// of imported modules, whose objects their functions can hand out.
+    classes: HashMap<String, ClassType>,
+    /// The variants of each enum, with the types of their fields, including the enums of
+    /// imported modules.
+    enums: HashMap<String, Vec<(Ident, Vec<Ty>)>>,
+    /// The variants without fields imported by name, which are values rather than functions.
+    values: Vec<(Ident, Ty)>,
+    /// The body of the function being checked, or None in the main program.
+    scope: Option<Span>,
+    /// Every function name resolved so far.
+    pub refs: Vec<Ref>,
+    /// The docstrings of the functions and methods checked so far, by the name each is
+    /// defined with.
+    pub docs: Vec<(Ident, String)>,
 }
 
-pub struct DefTypes(HashMap<Ident, ArrowType>);
+impl DefTypes {
+    /// Make a function that isn't defined in the source, like a builtin, known to the checker.
+    ///
+    /// # Errors
+    /// If its signature has more defaults than parameters.
+    ///
+    pub fn declare(&mut self, name: Ident, arrow: ArrowType) -> Result<(), Error> {
+        arrow.check_defaults(&name.to_string())?;
+        self.table.insert(name, arrow);
+        Ok(())
+    }
+
+    /// The scope of a function defined here, which can call the functions defined in this one.
+    fn nested(&self) -> Self {
+        let (mut table, mut enclosing) = (self.table.clone(), self.enclosing.clone());
+        let outer = if self.function {
+            &mut enclosing
+        } else {
+            &mut table
+        };
+        outer.extend(
+            self.locals
+                .iter()
+                .map(|(name, arrow)| (name.clone(), arrow.clone())),
+        );
+        Self {
+            table,
+            function: true,
+            enclosing,
+            modules: self.modules.clone(),
+            classes: self.classes.clone(),
+            enums: self.enums.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Take back the scope of a function defined here once it has checked, returning what it
+    /// captures, which this function has to capture too if they aren't its own.
+    fn unnest(&mut self, mut nested: Self) -> Vec<Ident> {
+        self.refs.append(&mut nested.refs);
+        self.docs.append(&mut nested.docs);
+        for name in &nested.captures {
+            if !self.locals.contains_key(name) && !self.captures.contains(name) {
+                self.captures.push(name.clone());
+            }
+        }
+        nested.captures
+    }
+
+    fn get_or(&mut self, name: &Ident) -> Result<&ArrowType, Error> {
+        let (decl, arrow) = if let Some(found) = self.locals.get_key_value(name) {
+            found
+        } else if let Some(found) = self.enclosing.get_key_value(name) {
+            if !self.captures.contains(name) {
+                self.captures.push(name.clone());
+            }
+            found
+        } else {
+            self.table
+                .get_key_value(name)
+                .ok_or_else(|| Error::new("undefined function").at(name.span()))?
+        };
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: decl.clone(),
+            desc: format!("{name}: {arrow}"),
+            scope: self.scope,
+        });
+        Ok(arrow)
+    }
+
+    /// Make a module's functions known to the checker, to be called by qualified name.
+    ///
+    /// # Errors
+    /// If the signature of one of them has more defaults than parameters.
+    ///
+    pub fn import(
+        &mut self,
+        module: Ident,
+        exports: HashMap<Ident, ArrowType>,
+    ) -> Result<(), Error> {
+        for (name, arrow) in &exports {
+            arrow.check_defaults(&format!("{module}.{name}"))?;
+        }
+        self.modules.insert(module, exports);
+        Ok(())
+    }
+
+    /// Look up a function, in the module it is qualified with if there is one.
+    fn get_qualified_or(&mut self, qual: Option<&Qual>, name: &Ident) -> Result<&ArrowType, Error> {
+        let Some(Qual { module, .. }) = qual else {
+            return self.get_or(name);
+        };
+        let (decl, exports) = self
+            .modules
+            .get_key_value(module)
+            .ok_or_else(|| Error::new("undefined module").at(module.span()))?;
+        let arrow = exports
+            .get(name)
+            .ok_or_else(|| Error::new("undefined function").at(name.span()))?;
+        // both names resolve to the import, since the definition is in another file
+        self.refs.push(Ref {
+            name: module.clone(),
+            decl: decl.clone(),
+            desc: format!("module {module}"),
+            scope: self.scope,
+        });
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: decl.clone(),
+            desc: format!("{module}.{name}: {arrow}"),
+            scope: self.scope,
+        });
+        Ok(arrow)
+    }
+
+    pub(crate) fn set(&mut self, name: Ident, arrow: ArrowType) {
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: name.clone(),
+            desc: format!("{name}: {arrow}"),
+            scope: self.scope,
+        });
+        // replace the key too, so that uses resolve to the latest definition
+        self.table.remove(&name);
+        self.table.insert(name, arrow);
+    }
+
+    /// Make a class known to the checker, along with its constructor, which takes a value for
+    /// each field.
+    fn set_class(&mut self, class: &Class) {
+        let fields = class
+            .fields()
+            .map(|field| (field.ident.clone(), (&field.ty).into()))
+            .collect();
+        let methods = class
+            .body
+            .methods
+            .iter()
+            .map(|method| (method.name.clone(), method.arrow_type()))
+            .collect();
+        self.classes
+            .insert(class.name.to_string(), ClassType { fields, methods });
+        self.set(class.name.clone(), class.arrow_type());
+    }
 
+    /// Make the classes and enums known to an imported module known here too, so that the
+    /// values its functions hand out can be used.
+    pub(crate) fn import_named(&mut self, module: &Self) {
+        for (name, class) in &module.classes {
+            self.classes
+                .entry(name.clone())
+                .or_insert_with(|| class.clone());
+        }
+        for (name, variants) in &module.enums {
+            self.enums
+                .entry(name.clone())
+                .or_insert_with(|| variants.clone());
+        }
+    }
+
+    /// Make a variant without fields imported from a module known here by its name.
+    pub(crate) fn import_value(&mut self, name: Ident, ty: Ty) {
+        self.values.push((name, ty));
+    }
+
+    /// Make an enum known to the checker, along with a constructor for each variant that has
+    /// fields.
+    fn set_enum(&mut self, enm: &Enum) {
+        let variants = enm.variants.iter().map(|variant| {
+            (
+                variant.name.clone(),
+                variant.fields().map(Ty::from).collect(),
+            )
+        });
+        self.enums.insert(enm.name.to_string(), variants.collect());
+        for (name, constructor) in enm.constructors() {
+            self.set(name, constructor);
+        }
+    }
+
+    /// The variants of an enum, if there is one by that name.
+    pub(crate) fn variants(&self, enm: &str) -> Option<&[(Ident, Vec<Ty>)]> {
+        self.enums.get(enm).map(Vec::as_slice)
+    }
+
+    /// The enum a variant is of, and the types of its fields, if there is one by that name.
+    fn variant(&mut self, name: &Ident) -> Option<(String, Vec<Ty>)> {
+        let (enm, decl, fields) = self.enums.iter().find_map(|(enm, variants)| {
+            let (decl, fields) = variants.iter().find(|(variant, _)| variant == name)?;
+            Some((enm, decl, fields))
+        })?;
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: decl.clone(),
+            desc: format!("{enm}.{name}"),
+            scope: self.scope,
+        });
+        Some((enm.clone(), fields.clone()))
+    }
+
+    /// The type of a field of a class, or of one of its methods, as a function that is already
+    /// given `self`.
+    fn member(&mut self, class: &str, name: &Ident) -> Result<Ty, Error> {
+        let missing = || Error::from(format!("`{class}` has no field or method `{name}`"));
+        let known = self
+            .classes
+            .get(class)
+            .ok_or_else(|| missing().at(name.span()))?;
+        let field = known.fields.iter().find(|(field, _)| field == name);
+        let (decl, ty) = match field {
+            Some((decl, ty)) => (decl, ty.clone()),
+            None => known
+                .methods
+                .get_key_value(name)
+                .map(|(decl, arrow)| (decl, arrow.into()))
+                .ok_or_else(|| missing().at(name.span()))?,
+        };
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: decl.clone(),
+            desc: format!("{class}.{name}: {ty}"),
+            scope: self.scope,
+        });
+        Ok(ty)
+    }
+
+    /// Make sure that every class a type names exists.
+    fn check_type(&self, ty: &Type) -> Result<(), Error> {
+        match ty {
+            Type::Named(name)
+                if !self.classes.contains_key(&name.to_string())
+                    && !self.enums.contains_key(&name.to_string()) =>
+            {
+                Err(Error::from(format!("unknown type `{name}`")).at(name.span()))
+            }
+            Type::List(_, item) | Type::Optional(_, item) => self.check_type(item),
+            Type::Func { params, ret, .. } => {
+                for param in params.iter() {
+                    self.check_type(param)?;
+                }
+                self.check_type(ret)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Remember the docstring of a function or method, if its body opens with one.
+    fn document(&mut self, name: &Ident, rule: &Nest) {
+        if let Some(doc) = rule.doc() {
+            self.docs.push((name.clone(), doc.to_string()));
+        }
+    }
+
+    /// Make a function defined in a block known to the rest of the block.
+    fn set_local(&mut self, name: Ident, arrow: ArrowType) {
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: name.clone(),
+            desc: format!("{name}: {arrow}"),
+            scope: self.scope,
+        });
+        self.locals.remove(&name);
+        self.locals.insert(name, arrow);
+    }
+}
+
+/// The scopes of a function with the given body, defined where the given ones are.
+fn nested(defs: &DefTypes, syms: &SymTab, body: Span) -> (DefTypes, SymTab) {
+    let (mut defs, mut syms) = (defs.nested(), syms.nested());
+    defs.scope = Some(body);
+    syms.scope = Some(body);
+    (defs, syms)
+}
+
+/// Take back the scopes of a function defined here once it has checked, returning the
+/// variables and functions it captures.
+fn unnest(defs: &mut DefTypes, syms: &mut SymTab, inner: (DefTypes, SymTab)) -> Captures {
+    let (inner_defs, locals) = inner;
+    let mut captures = syms.unnest(locals);
+    captures.extend(defs.unnest(inner_defs));
+    Captures(captures)
+}
+
+impl ArrowType {
+    /// Make sure a signature the host wrote for a function can be called: that it doesn't leave
+    /// out more parameters than it has.
+    ///
+    /// # Errors
+    /// If it has more defaults than parameters.
+    ///
+    pub fn check_defaults(&self, name: &str) -> Result<(), Error> {
+        if self.defaults > self.params.len() {
+            let msg = format!("`{name}` has more defaults than parameters");
+            return Err(Error::from(msg));
+        }
+        Ok(())
+    }
+
+    /// Check the argument types of a call against this signature.
+    fn check_call<'a>(
+        &self,
+        args: impl ExactSizeIterator<Item = &'a mut Expn>,
+        defs: &mut DefTypes,
+        syms: &mut SymTab,
+    ) -> Result<(), Error> {
+        let required = self.params.len() - self.defaults;
+        if !(required..=self.params.len()).contains(&args.len()) {
+            return Err("unexpected number of arguments".into());
+        }
+        for (arg, param) in args.zip(&self.params) {
+            param.expect(&arg.check(defs, syms)?, "mismatched argument type")?;
+        }
+        Ok(())
+    }
+}
+
+/// The variables in scope, and how the code being checked can use them.
+#[derive(Default)]
 pub struct SymTab {
     table: HashMap<Ident, Ty>,
+    /// Whether this is the scope of a function's body, rather than of the main program.
+    function: bool,
+    /// The variables of the functions this one is nested in, which it captures by using them.
+    enclosing: HashMap<Ident, Ty>,
+    /// In a function, the global variables it can see. In the main program, the globals it
+    /// declares at its top level, which its functions can see.
+    globals: HashMap<Ident, Ty>,
+    /// The variables this function captures, in the order it first uses them.
+    captures: Vec<Ident>,
+    /// The variables declared `nonlocal` or `global` here, which it can assign to.
+    declared: Vec<Ident>,
+    /// The types that variables which may be None are known to have at this point, like `int`
+    /// after checking that an `Optional[int]` is not None.
+    narrowed: HashMap<Ident, Ty>,
+    /// The body of the function being checked, or None in the main program.
+    scope: Option<Span>,
+    /// Every variable name resolved so far.
+    pub refs: Vec<Ref>,
 }
 
 impl SymTab {
-    fn get(&mut self, name: &Ident) -> Option<Ty> {
-        self.table.get(name).copied()
+    /// The scope of a function defined here, which sees the variables of this one.
+    fn nested(&self) -> Self {
+        let (mut enclosing, mut globals) = (self.enclosing.clone(), self.globals.clone());
+        let outer = if self.function {
+            &mut enclosing
+        } else {
+            &mut globals
+        };
+        outer.extend(
+            self.table
+                .iter()
+                .map(|(name, ty)| (name.clone(), ty.clone())),
+        );
+        Self {
+            function: true,
+            enclosing,
+            globals,
+            ..Self::default()
+        }
+    }
+
+    /// Take back the scope of a function defined here once it has checked, returning what it
+    /// captures, which this function has to capture too if they aren't its own.
+    fn unnest(&mut self, mut nested: Self) -> Vec<Ident> {
+        self.refs.append(&mut nested.refs);
+        for name in &nested.captures {
+            if !self.table.contains_key(name) && !self.captures.contains(name) {
+                self.captures.push(name.clone());
+            }
+        }
+        nested.captures
+    }
+
+    /// Whether a variable is in scope.
+    fn contains(&self, name: &Ident) -> bool {
+        let outer = self.enclosing.contains_key(name) || self.globals.contains_key(name);
+        self.table.contains_key(name) || self.function && outer
     }
 
     fn get_or(&mut self, name: &Ident) -> Result<Ty, Error> {
-        self.get(name).ok_or("undefined variable")
+        let ty = self.lookup(name)?;
+        Ok(self.narrowed.get(name).cloned().unwrap_or(ty))
+    }
+
+    /// The type a variable was declared with.
+    fn lookup(&mut self, name: &Ident) -> Result<Ty, Error> {
+        let found = if let Some(found) = self.table.get_key_value(name) {
+            found
+        } else if let Some(found) = self.enclosing.get_key_value(name) {
+            if !self.captures.contains(name) {
+                self.captures.push(name.clone());
+            }
+            found
+        } else if let Some(found) = self.globals.get_key_value(name).filter(|_| self.function) {
+            found
+        } else {
+            return Err(Error::new("undefined variable").at(name.span()));
+        };
+        let (decl, ty) = found;
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: decl.clone(),
+            desc: format!("{name}: {ty}"),
+            scope: self.scope,
+        });
+        Ok(ty.clone())
+    }
+
+    /// The type of a variable that is being assigned to, which must be this function's own
+    /// unless it says otherwise.
+    fn get_mut_or(&mut self, name: &Ident) -> Result<Ty, Error> {
+        let own = !self.function || self.table.contains_key(name) || self.declared.contains(name);
+        let kind = if self.enclosing.contains_key(name) {
+            "nonlocal"
+        } else {
+            "global"
+        };
+        if !own && (self.enclosing.contains_key(name) || self.globals.contains_key(name)) {
+            let msg = format!("assigning to `{name}` needs `{kind} {name};` first");
+            return Err(Error::from(msg).at(name.span()));
+        }
+        self.lookup(name)
+    }
+
+    /// Note that a variable declared with a type has just been assigned a value of another,
+    /// which it is known to have until it is assigned again.
+    fn assigned(&mut self, name: &Ident, declared: &Ty, ty: Ty) {
+        match declared {
+            Ty::Optional(_) if ty == Ty::Unit => {
+                self.narrowed.insert(name.clone(), ty);
+            }
+            Ty::Optional(inner) if inner.accepts(&ty) => {
+                self.narrowed.insert(name.clone(), (**inner).clone());
+            }
+            _ => {
+                self.narrowed.remove(name);
+            }
+        }
+    }
+
+    /// The variable that a condition like `x is None` or `x is not None` tests, if it may be
+    /// None, with the types it has when the condition holds and when it doesn't.
+    fn none_test(&self, cond: &Expn) -> Option<(Ident, Ty, Ty)> {
+        let (name, is_none) = none_test(cond)?;
+        let ty = self.narrowed.get(name).or_else(|| self.table.get(name));
+        let ty = ty.or_else(|| self.enclosing.get(name));
+        let ty = ty.or_else(|| self.globals.get(name).filter(|_| self.function))?;
+        let Ty::Optional(inner) = ty else {
+            return None;
+        };
+        let (when, otherwise) = if is_none {
+            (Ty::Unit, (**inner).clone())
+        } else {
+            ((**inner).clone(), Ty::Unit)
+        };
+        Some((name.clone(), when, otherwise))
+    }
+
+    /// Forget what is known about the variables that statements assign to, which might run
+    /// any number of times.
+    fn forget_assigned(&mut self, stmts: &[Stmt]) {
+        let mut names = Vec::new();
+        assigned_in(stmts, &mut names);
+        for name in names {
+            self.narrowed.remove(&name);
+        }
     }
 
     fn set(&mut self, name: Ident, val: impl Into<Ty>) {
-        self.table.insert(name, val.into());
+        let ty = val.into();
+        self.refs.push(Ref {
+            name: name.clone(),
+            decl: name.clone(),
+            desc: format!("{name}: {ty}"),
+            scope: self.scope,
+        });
+        // replace the key too, so that uses resolve to the latest declaration
+        self.table.remove(&name);
+        self.narrowed.remove(&name);
+        self.table.insert(name, ty);
+    }
+
+    /// Declare a variable of this function's own, which must not also be one from outside it.
+    fn declare(&mut self, name: Ident, val: impl Into<Ty>) -> Result<(), Error> {
+        if self.declared.contains(&name) || self.captures.contains(&name) {
+            let msg = format!("`{name}` is already a variable from outside this function");
+            return Err(Error::from(msg).at(name.span()));
+        }
+        self.set(name, val);
+        Ok(())
+    }
+
+    /// Check a `nonlocal` or `global` statement, which lets this function assign to a variable
+    /// from outside it.
+    fn declare_outer(&mut self, name: &Ident, nonlocal: bool) -> Result<(), Error> {
+        let kind = if nonlocal { "nonlocal" } else { "global" };
+        if !self.function {
+            // the main program's variables are already global
+            return if nonlocal {
+                Err(Error::new("`nonlocal` is only allowed in a function").at(name.span()))
+            } else {
+                Ok(())
+            };
+        }
+        if self.table.contains_key(name) {
+            let msg = format!("`{name}` is already a variable of this function");
+            return Err(Error::from(msg).at(name.span()));
+        }
+        let outer = if nonlocal {
+            &self.enclosing
+        } else {
+            &self.globals
+        };
+        if !outer.contains_key(name) {
+            return Err(Error::from(format!("no {kind} variable `{name}`")).at(name.span()));
+        }
+        if nonlocal {
+            // look it up, so that it is captured
+            self.get_or(name)?;
+        }
+        self.declared.push(name.clone());
+        Ok(())
     }
 }
 
-impl Check for Defn {
-    type Info = ArrowType;
+impl Check for Prgm {
+    type Info = ();
 
     fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error> {
-        let returns = (self.rule.check(defs, syms)?, self.ret);
-        // convert ft to unit
-        let actual = match returns.0 {
-            Rtns::Fallthrough => Ty::Unit,
-            Rtns::MightReturn(_) => { return Err("fuction blocks must return a definite value"); }
-            Rtns::Returns(ty) => ty, 
-        };
-        
-        let expected = match *self.ret {
-            None => ty,
-            Some(ret) => ret.ty.into,
-        };
-        
-        if actual != expected {
-            for param in self.params {
-                // fucking hell
+        self.check_all(defs, syms)
+            .into_iter()
+            .next()
+            .map_or(Ok(()), Err)
+    }
+}
+
+impl Prgm {
+    /// Check a program, carrying on past a class or definition with a problem to find the
+    /// problems in the others, in the order they appear.
+    pub fn check_all(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Vec<Error> {
+        if let Err(e) = self.declare(defs, syms) {
+            return vec![e];
+        }
+        let mut errors = Vec::new();
+        for class in &mut self.classes {
+            errors.extend(class.check(defs, syms).err());
+        }
+        for defn in &mut self.defns {
+            let checked = defn.check(defs, syms);
+            errors.extend(checked.err().map(|e| e.at(defn.name.span())));
+        }
+        errors.extend(self.main.check(defs, syms).err());
+        errors
+    }
+
+    /// Register every type, signature and global up front, so that definitions can refer to
+    /// each other.
+    fn declare(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<(), Error> {
+        // the variants imported by name, like those of the program's own enums, are values
+        for (name, ty) in defs.values.drain(..) {
+            syms.set(name, ty);
+        }
+        for enm in &mut self.enums {
+            enm.check(defs, syms)?;
+        }
+        for class in self.classes.iter() {
+            defs.set_class(class);
+        }
+        // which the fields of variants can be, now that they are all known
+        for variant in self.enums.iter().flat_map(|enm| enm.variants.iter()) {
+            for ty in variant.fields() {
+                defs.check_type(ty)?;
             }
+        }
+        for defn in self.defns.iter() {
+            defs.set(defn.name.clone(), defn.arrow_type());
+        }
+        // and every global, so that definitions can use them, as long as they are declared by
+        // the time the definitions run
+        for stmt in self.main.stmts.iter() {
+            if let Stmt::Decl { typed_ident, .. } = stmt {
+                let ty = (&typed_ident.ty).into();
+                syms.globals.entry(typed_ident.ident.clone()).or_insert(ty);
+            }
+        }
+        Ok(())
+    }
+}
 
-            return Error
-        } else {
+impl Check for Enum {
+    type Info = ();
+
+    fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error> {
+        let name = self.name.to_string();
+        if defs.variants(&name).is_some() || defs.classes.contains_key(&name) {
+            let msg = format!("there is already a type `{name}`");
+            return Err(Error::from(msg).at(self.name.span()));
+        }
+        if self.variants.is_empty() {
+            return Err(Error::new("an enum needs at least one variant").at(self.name.span()));
+        }
+        for (i, variant) in self.variants.iter().enumerate() {
+            let earlier = self
+                .variants
+                .iter()
+                .take(i)
+                .any(|other| other.name == variant.name);
+            let known = defs
+                .enums
+                .values()
+                .flatten()
+                .any(|(other, _)| *other == variant.name);
+            if earlier || known {
+                let msg = format!("there is already a variant `{}`", variant.name);
+                return Err(Error::from(msg).at(variant.name.span()));
+            }
+        }
+        defs.set_enum(self);
+        for variant in self.variants.iter() {
+            if variant.fields.as_prefix().is_none() {
+                // a variant without fields is a value of its own, which the program can use
+                syms.set(variant.name.clone(), Ty::Named(name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Enum {
+    /// The signatures of the constructors of its variants that have fields, by name.
+    pub fn constructors(&self) -> impl Iterator<Item = (Ident, ArrowType)> + '_ {
+        let variants = self.variants.iter();
+        variants
+            .filter(|variant| variant.fields.as_prefix().is_some())
+            .map(|variant| {
+                let constructor = ArrowType {
+                    return_type: Some(Ty::Named(self.name.to_string())),
+                    params: variant.fields().map(Ty::from).collect(),
+                    defaults: 0,
+                };
+                (variant.name.clone(), constructor)
+            })
+    }
+}
+
+impl Class {
+    /// The signature of this class's constructor, which takes a value for each field.
+    pub fn arrow_type(&self) -> ArrowType {
+        ArrowType {
+            return_type: Some(Ty::Named(self.name.to_string())),
+            params: self.fields().map(|field| (&field.ty).into()).collect(),
+            defaults: 0,
+        }
+    }
+}
+
+impl Check for Class {
+    type Info = ();
+
+    fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error> {
+        let class = &self.name;
+        let mut seen: Vec<Ident> = Vec::new();
+        let fields = self
+            .body
+            .fields
+            .iter()
+            .map(|field| &field.typed_ident.ident);
+        let methods = self.body.methods.iter().map(|method| &method.name);
+        for name in fields.chain(methods) {
+            if seen.contains(name) {
+                let msg = format!("`{class}` already has a field or method `{name}`");
+                return Err(Error::from(msg).at(name.span()));
+            }
+            seen.push(name.clone());
+        }
+        for field in self.body.fields.iter() {
+            defs.check_type(&field.typed_ident.ty)?;
+        }
 
+        for method in &mut self.body.methods {
+            let arrow = method.arrow_type();
+            let shows = arrow.params.is_empty() && arrow.return_type == Some(Ty::Str);
+            if method.name == "__str__" && !shows {
+                let msg = "`__str__` must take only `self` and return a str";
+                return Err(Error::new(msg).at(method.name.span()));
+            }
+            let this = (method.self_ident(), Ty::Named(class.to_string()));
+            let params: Vec<_> = method.params().cloned().collect();
+            let ret = method.ret.as_prefix();
+            defs.document(&method.name, &method.rule);
+            check_function(Some(this), &params, ret, &mut method.rule, defs, syms)
+                .map_err(|e| e.at(method.name.span()))?;
         }
+        Ok(())
+    }
+}
+
+impl Method {
+    /// The signature of this method, leaving out `self`.
+    pub fn arrow_type(&self) -> ArrowType {
+        ArrowType {
+            return_type: self.ret.as_prefix().map(|ret| (&ret.ty).into()),
+            params: self.params().map(|param| (&param.ty).into()).collect(),
+            defaults: 0,
+        }
+    }
+}
+
+impl Defn {
+    /// The declared signature of this definition.
+    pub fn arrow_type(&self) -> ArrowType {
+        ArrowType {
+            return_type: self.ret.as_prefix().map(|ret| (&ret.ty).into()),
+            params: self.params.iter().map(|param| (&param.ty).into()).collect(),
+            defaults: 0,
+        }
+    }
+}
+
+impl Check for Defn {
+    type Info = ArrowType;
+
+    fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error> {
+        let params: Vec<_> = self.params.iter().cloned().collect();
+        let ret = self.ret.as_prefix();
+        defs.document(&self.name, &self.rule);
+        self.captures = check_function(None, &params, ret, &mut self.rule, defs, syms)?;
+        Ok(self.arrow_type())
+    }
+}
+
+/// Check the body of a function with the given parameters, after the object it is called on if
+/// it is a method, against its declared return type. Returns what it captures.
+fn check_function(
+    this: Option<(Ident, Ty)>,
+    params: &[TypedIdent],
+    ret: Option<&ReturnType>,
+    rule: &mut Nest,
+    defs: &mut DefTypes,
+    syms: &mut SymTab,
+) -> Result<Captures, Error> {
+    for param in params {
+        defs.check_type(&param.ty)?;
+    }
+    if let Some(ret) = ret {
+        defs.check_type(&ret.ty)?;
+    }
+
+    let (mut inner_defs, mut locals) = nested(defs, syms, rule.span());
+    if let Some((name, ty)) = this {
+        locals.set(name, ty);
+    }
+    for param in params {
+        locals.set(param.ident.clone(), &param.ty);
+    }
+
+    let expected = ret.map_or(Ty::Unit, |ret| (&ret.ty).into());
+    let rtns = rule.block.check_stmts(true, &mut inner_defs, &mut locals);
+    let captures = unnest(defs, syms, (inner_defs, locals));
+    let actual = match rtns? {
+        Rtns::Diverges => expected.clone(),
+        Rtns::Fallthrough => Ty::Unit,
+        // falling off the end returns None, which is only fine if that's what it may return
+        Rtns::MightReturn(ty @ (Ty::Unit | Ty::Optional(_))) => ty,
+        Rtns::MightReturn(_) => return Err("function blocks must return a definite value".into()),
+        Rtns::Returns(ty) => ty,
+    };
+    expected.expect(&actual, "mismatched return type")?;
+    Ok(captures)
 }
 
 impl Check for Nest {
@@ -157,14 +1117,38 @@ impl Check for Blck {
     type Info = Rtns;
 
     fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error> {
+        self.check_stmts(false, defs, syms)
+    }
+}
+
+impl Blck {
+    /// Check the statements of a block, which may open with a docstring if it is the body of a
+    /// function.
+    fn check_stmts(
+        &mut self,
+        body: bool,
+        defs: &mut DefTypes,
+        syms: &mut SymTab,
+    ) -> Result<Rtns, Error> {
+        for (i, stmt) in self.stmts.iter().enumerate() {
+            if matches!(stmt, Stmt::Doc(..)) && !(body && i == 0) {
+                let msg = "a docstring must be the first statement of a function body";
+                return Err(Error::new(msg).at(stmt.span()));
+            }
+        }
+        // functions defined in a block can call each other, but only inside it
+        let outer = defs.locals.clone();
+        for stmt in self.stmts.iter() {
+            if let Stmt::Defn(defn) = stmt {
+                defs.set_local(defn.name.clone(), defn.arrow_type());
+            }
+        }
         let mut rtns = Rtns::Fallthrough;
         for stmt in &mut self.stmts {
-            if let Rtns::Returns(_) = rtns {
-                // already returned, but we have more code
-                return Err("unexpected statement; already returned");
-            }
-            rtns = rtns.reconcile(stmt.check(defs, syms)?)?;
+            let info = stmt.check(defs, syms).map_err(|e| e.at(stmt.span()))?;
+            rtns = rtns.then(info).map_err(|e| e.at(stmt.span()))?;
         }
+        defs.locals = outer;
         Ok(rtns)
     }
 }
@@ -177,33 +1161,74 @@ impl Check for Stmt {
             Stmt::Decl {
                 typed_ident, expn, ..
             } => {
+                defs.check_type(&typed_ident.ty)?;
                 let expected: Ty = (&typed_ident.ty).into();
                 let actual = expn.check(defs, syms)?;
-                if expected == actual {
-                    syms.set(typed_ident.ident.clone(), actual);
-                    Rtns::Fallthrough
-                } else {
-                    return Err("mismatched types");
-                }
+                expected.expect(&actual, "mismatched types")?;
+                syms.declare(typed_ident.ident.clone(), expected.clone())?;
+                syms.assigned(&typed_ident.ident, &expected, actual);
+                Rtns::Fallthrough
             }
             Stmt::Assgn { ident, expn, .. } => {
-                let expected = syms.get_or(ident)?;
+                let expected = syms.get_mut_or(ident)?;
                 let actual = expn.check(defs, syms)?;
-                if expected == actual {
-                    Rtns::Fallthrough
-                } else {
-                    return Err("mismatched types");
-                }
+                expected.expect(&actual, "mismatched types")?;
+                syms.assigned(ident, &expected, actual);
+                Rtns::Fallthrough
             }
             Stmt::Updt { ident, expn, .. } => {
-                syms.get_or(ident)?.expect_int()?;
+                syms.get_mut_or(ident)?.expect_int()?;
                 expn.check(defs, syms)?.expect_int()?;
                 Rtns::Fallthrough
             }
-            Stmt::Pass(_, _) => Rtns::Fallthrough,
+            Stmt::SetField { target, expn, .. } => {
+                resolve_receiver(target, syms);
+                let span = target.span();
+                let Some((Postfix::Field(_, name), postfixes)) = target.postfixes.split_last_mut()
+                else {
+                    return Err(Error::new("can only assign to a variable or a field").at(span));
+                };
+                let ty = target.leaf.check(defs, syms)?;
+                let expected = match check_postfixes(ty, postfixes, defs, syms)? {
+                    Ty::Named(class) => defs.member(&class, name)?,
+                    other => return Err(other.expected("object")),
+                };
+                expected.expect(&expn.check(defs, syms)?, "mismatched types")?;
+                Rtns::Fallthrough
+            }
+            Stmt::Pass(_, _) | Stmt::Doc(_, _) => Rtns::Fallthrough,
+            Stmt::Defn(defn) => {
+                defn.check(defs, syms).map_err(|e| e.at(defn.name.span()))?;
+                Rtns::Fallthrough
+            }
             Stmt::Print(_, args, _) => {
+                let mut keywords: Vec<String> = Vec::new();
                 for arg in args.iter_mut() {
-                    arg.check(defs, syms)?.expect_str()?;
+                    match arg {
+                        // anything can be printed
+                        PrintArg::Pos(expn) if keywords.is_empty() => {
+                            expn.check(defs, syms)?;
+                        }
+                        PrintArg::Pos(expn) => {
+                            let msg = "positional argument follows keyword argument";
+                            return Err(Error::new(msg).at(expn.span()));
+                        }
+                        PrintArg::Kw { name, value, .. } => {
+                            let keyword = name.to_string();
+                            let msg = if !["sep", "end"].contains(&keyword.as_str()) {
+                                Some("unexpected keyword argument")
+                            } else if keywords.contains(&keyword) {
+                                Some("repeated keyword argument")
+                            } else {
+                                None
+                            };
+                            if let Some(msg) = msg {
+                                return Err(Error::new(msg).at(name.span()));
+                            }
+                            value.check(defs, syms)?.expect_str()?;
+                            keywords.push(keyword);
+                        }
+                    }
                 }
                 Rtns::Fallthrough
             }
@@ -214,26 +1239,357 @@ impl Check for Stmt {
                 ..
             } => {
                 cond.check(defs, syms)?.expect_bool()?;
+                let test = syms.none_test(cond);
+                let before = syms.narrowed.clone();
+                if let Some((name, when, _)) = &test {
+                    syms.narrowed.insert(name.clone(), when.clone());
+                }
                 let if_ret = if_nest.check(defs, syms)?;
+                let after_if = std::mem::replace(&mut syms.narrowed, before);
+                if let Some((name, _, otherwise)) = test {
+                    syms.narrowed.insert(name, otherwise);
+                }
                 let else_ret = else_nest.check(defs, syms)?;
+                let after_else = std::mem::take(&mut syms.narrowed);
+                syms.narrowed = join_narrowed([(after_if, &if_ret), (after_else, &else_ret)]);
                 if_ret.reconcile(else_ret)?
             }
+            Stmt::Match { expn, cases, .. } => {
+                let ty = expn.check(defs, syms)?;
+                // like an if, the match returns what its cases do, unless none of them runs
+                let mut rtns: Option<Rtns> = None;
+                let before = syms.narrowed.clone();
+                let mut after = Vec::new();
+                for case in cases.iter_mut() {
+                    check_pattern(&mut case.pattern, &ty, defs, syms)?;
+                    let case_rtns = case.nest.check(defs, syms)?;
+                    let narrowed = std::mem::replace(&mut syms.narrowed, before.clone());
+                    after.push((narrowed, case_rtns.clone()));
+                    rtns = Some(match rtns {
+                        Some(rtns) => rtns.reconcile(case_rtns)?,
+                        None => case_rtns,
+                    });
+                }
+                pattern::check_cases(cases, &ty, expn.span(), defs)?;
+                syms.narrowed = join_narrowed(after);
+                rtns.unwrap_or(Rtns::Fallthrough)
+            }
             Stmt::While { cond, nest, .. } => {
+                // the condition is checked again after each time the nest runs
+                syms.forget_assigned(&nest.block.stmts);
+                cond.check(defs, syms)?.expect_bool()?;
+                let test = syms.none_test(cond);
+                let before = syms.narrowed.clone();
+                if let Some((name, when, _)) = &test {
+                    syms.narrowed.insert(name.clone(), when.clone());
+                }
+                let rtns = nest.check(defs, syms)?.reconcile(Rtns::Fallthrough)?;
+                syms.narrowed = before;
+                if let Some((name, _, otherwise)) = test {
+                    syms.narrowed.insert(name, otherwise);
+                }
+                rtns
+            }
+            Stmt::For {
+                var, iter, nest, ..
+            } => {
+                let item = match iter.check(defs, syms)? {
+                    Ty::List(item) => *item,
+                    Ty::Str => Ty::Str,
+                    other => return Err(other.expected("list or str")),
+                };
+                syms.forget_assigned(&nest.block.stmts);
+                let before = syms.narrowed.clone();
+                syms.declare(var.clone(), item)?;
+                let rtns = nest.check(defs, syms)?.reconcile(Rtns::Fallthrough)?;
+                syms.narrowed = before;
+                rtns
+            }
+            Stmt::Assert { cond, msg, .. } => {
                 cond.check(defs, syms)?.expect_bool()?;
-                nest.check(defs, syms)?.reconcile(Rtns::Fallthrough)?
+                if let Some(msg) = msg.as_mut() {
+                    msg.check(defs, syms)?.expect_str()?;
+                }
+                Rtns::Fallthrough
+            }
+            Stmt::Raise { class, msg, .. } => {
+                exn_class(class)?;
+                msg.check(defs, syms)?.expect_str()?;
+                Rtns::Diverges
+            }
+            Stmt::Try {
+                nest,
+                handlers,
+                finally,
+                ..
+            } => {
+                if handlers.is_empty() && finally.as_prefix().is_none() {
+                    return Err("try needs an except or a finally".into());
+                }
+                // a handler can take over from anywhere in the nest, so it only knows what was
+                // known before the nest, and only of what the nest doesn't assign to
+                let before = syms.narrowed.clone();
+                let mut rtns = nest.check(defs, syms)?;
+                syms.narrowed = before;
+                syms.forget_assigned(&nest.block.stmts);
+                for handler in handlers.iter_mut() {
+                    if let Some(class) = handler.class.as_prefix() {
+                        exn_class(class)?;
+                    }
+                    let before = syms.narrowed.clone();
+                    if let Some(name) = handler.name() {
+                        syms.declare(name.clone(), Ty::Str)?;
+                    }
+                    rtns = rtns.reconcile(handler.nest.check(defs, syms)?)?;
+                    syms.narrowed = before;
+                    syms.forget_assigned(&handler.nest.block.stmts);
+                }
+                match finally.as_mut() {
+                    Some(finally) => {
+                        let before = syms.narrowed.clone();
+                        let rtns = rtns.then_finally(finally.check(defs, syms)?)?;
+                        syms.narrowed = before;
+                        syms.forget_assigned(&finally.block.stmts);
+                        rtns
+                    }
+                    None => rtns,
+                }
+            }
+            Stmt::Nonlocal { names, .. } => {
+                for name in names.iter() {
+                    syms.declare_outer(name, true)?;
+                }
+                Rtns::Fallthrough
+            }
+            Stmt::Global { names, .. } => {
+                for name in names.iter() {
+                    syms.declare_outer(name, false)?;
+                }
+                Rtns::Fallthrough
             }
             Stmt::ReturnExpn { expn, .. } => Rtns::Returns(expn.check(defs, syms)?),
             Stmt::Return { .. } => Rtns::Returns(Ty::Unit),
-            Stmt::FuncCall { name, args, end } => todo!(), // TODO: hard
+            Stmt::Call(call, _) => {
+                resolve_receiver(call, syms);
+                let span = call.span();
+                // the return value, if any, is discarded
+                let (arrow, args) = match (&mut call.leaf, call.postfixes.split_last_mut()) {
+                    (leaf, Some((Postfix::Call(args), postfixes))) => {
+                        let ty = leaf.check(defs, syms)?;
+                        (check_postfixes(ty, postfixes, defs, syms)?.arrow()?, args)
+                    }
+                    (Leaf::FuncCall { module, name, args }, None) => {
+                        (callee(module.as_prefix(), name, defs, syms)?, args)
+                    }
+                    _ => return Err(Error::new("only a call can be a statement").at(span)),
+                };
+                arrow.check_call(args.iter_mut().map(Box::as_mut), defs, syms)?;
+                Rtns::Fallthrough
+            }
         })
     }
 }
 
+/// The signature of the function a call names, which may be a variable holding one.
+fn callee(
+    module: Option<&Qual>,
+    name: &Ident,
+    defs: &mut DefTypes,
+    syms: &mut SymTab,
+) -> Result<ArrowType, Error> {
+    // like at runtime, a variable shadows a function with the same name
+    if module.is_none() && syms.contains(name) {
+        return syms.get_or(name)?.arrow().map_err(|e| e.at(name.span()));
+    }
+    defs.get_qualified_or(module, name).cloned()
+}
+
+/// Read a call like `c.area()`, which parses as a call of a function from a module `c`, as a
+/// call of a method of the object a variable `c` holds, when there is one. A variable shadows a
+/// module with the same name, and only the checker knows which names are variables.
+fn resolve_receiver(indx: &mut Indx, syms: &SymTab) {
+    let Leaf::FuncCall { module, .. } = &indx.leaf else {
+        return;
+    };
+    let Some(qual) = module
+        .as_prefix()
+        .filter(|qual| syms.contains(&qual.module))
+    else {
+        return;
+    };
+    let receiver = Leaf::Name(qual.module.clone());
+    let Leaf::FuncCall { module, name, args } = std::mem::replace(&mut indx.leaf, receiver) else {
+        unreachable!("the leaf is a call");
+    };
+    let dot = module.into_prefix().expect("the call is qualified").dot;
+    let method = [Postfix::Field(dot, name), Postfix::Call(args)];
+    indx.postfixes.splice(0..0, method);
+}
+
+/// Check that a pattern can match a value of the given type, declaring the variables it binds.
+fn check_pattern(
+    pattern: &mut Pattern,
+    ty: &Ty,
+    defs: &mut DefTypes,
+    syms: &mut SymTab,
+) -> Result<(), Error> {
+    let span = pattern.span();
+    let expected = match pattern {
+        Pattern::Wildcard(_) => return Ok(()),
+        Pattern::Nmbr(_) => Ty::Int,
+        Pattern::Strg(_) => Ty::Str,
+        Pattern::Bool(_) => Ty::Bool,
+        Pattern::Name(name, is_variant) => match defs.variant(name) {
+            Some((_, fields)) if !fields.is_empty() => {
+                let msg = format!("`{name}` needs a pattern for each of its fields");
+                return Err(Error::from(msg).at(span));
+            }
+            Some((enm, _)) => {
+                is_variant.0 = true;
+                Ty::Named(enm)
+            }
+            // anything else is a name for the whole value
+            None => return syms.declare(name.clone(), ty.clone()),
+        },
+        Pattern::Variant { name, fields } => {
+            let (enm, tys) = defs
+                .variant(name)
+                .ok_or_else(|| Error::from(format!("unknown variant `{name}`")).at(name.span()))?;
+            if fields.len() != tys.len() {
+                let msg = format!("`{name}` needs a pattern for each of its fields");
+                return Err(Error::from(msg).at(span));
+            }
+            for (field, ty) in fields.iter_mut().zip(&tys) {
+                check_pattern(field, ty, defs, syms)?;
+            }
+            Ty::Named(enm)
+        }
+    };
+    if ty.accepts(&expected) {
+        Ok(())
+    } else {
+        Err(Error::new("mismatched types").at(span))
+    }
+}
+
+/// The variable that a condition like `x is None`, `x == None` or `x is not None` tests, and
+/// whether the condition holds when it is None.
+fn none_test(cond: &Expn) -> Option<(&Ident, bool)> {
+    // just a leaf, like `x`, with nothing else around it
+    fn leaf(sum: &Sum) -> Option<&Leaf> {
+        match sum {
+            LeftAssoc::Rhs(LeftAssoc::Rhs(LeftAssoc::Rhs(UnExp::Child(indx))))
+                if indx.postfixes.is_empty() =>
+            {
+                Some(&indx.leaf)
+            }
+            _ => None,
+        }
+    }
+
+    let LeftAssoc::Rhs(LeftAssoc::Rhs(RightAssoc::Binary { lhs, op, rhs })) = &cond.0 else {
+        return None;
+    };
+    let RightAssoc::Lhs(rhs) = &**rhs else {
+        return None;
+    };
+    let is_none = match op {
+        Comp::Is(_) | Comp::Eq(_) => true,
+        Comp::IsNot(_, _) => false,
+        Comp::Leq(_) | Comp::Lt(_) => return None,
+    };
+    match (leaf(lhs)?, leaf(rhs)?) {
+        (Leaf::Name(name), Leaf::Unit(_)) | (Leaf::Unit(_), Leaf::Name(name)) => {
+            Some((name, is_none))
+        }
+        _ => None,
+    }
+}
+
+/// Add the names of the variables that statements assign to, or declare, to the list.
+fn assigned_in(stmts: &[Stmt], names: &mut Vec<Ident>) {
+    fn bound_in(pattern: &Pattern, names: &mut Vec<Ident>) {
+        match pattern {
+            Pattern::Name(name, _) => names.push(name.clone()),
+            Pattern::Variant { fields, .. } => {
+                for field in fields.iter() {
+                    bound_in(field, names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for stmt in stmts {
+        match stmt {
+            Stmt::Decl { typed_ident, .. } => names.push(typed_ident.ident.clone()),
+            Stmt::Assgn { ident, .. } | Stmt::Updt { ident, .. } => names.push(ident.clone()),
+            Stmt::If {
+                if_nest, else_nest, ..
+            } => {
+                assigned_in(&if_nest.block.stmts, names);
+                assigned_in(&else_nest.block.stmts, names);
+            }
+            Stmt::Match { cases, .. } => {
+                for case in cases.iter() {
+                    bound_in(&case.pattern, names);
+                    assigned_in(&case.nest.block.stmts, names);
+                }
+            }
+            Stmt::While { nest, .. } => assigned_in(&nest.block.stmts, names),
+            Stmt::For { var, nest, .. } => {
+                names.push(var.clone());
+                assigned_in(&nest.block.stmts, names);
+            }
+            Stmt::Try {
+                nest,
+                handlers,
+                finally,
+                ..
+            } => {
+                assigned_in(&nest.block.stmts, names);
+                for handler in handlers.iter() {
+                    names.extend(handler.name().cloned());
+                    assigned_in(&handler.nest.block.stmts, names);
+                }
+                if let Some(finally) = finally.as_ref() {
+                    assigned_in(&finally.block.stmts, names);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// What is known about variables after one of some branches has run, from what was known at
+/// the end of each and how it ended: only what all those that carry on from there agree on.
+fn join_narrowed<R: std::borrow::Borrow<Rtns>>(
+    branches: impl IntoIterator<Item = (HashMap<Ident, Ty>, R)>,
+) -> HashMap<Ident, Ty> {
+    let mut ends = branches
+        .into_iter()
+        .filter(|(_, rtns)| matches!(rtns.borrow(), Rtns::Fallthrough | Rtns::MightReturn(_)));
+    let Some((mut joined, _)) = ends.next() else {
+        // nothing runs after them
+        return HashMap::new();
+    };
+    for (narrowed, _) in ends {
+        joined.retain(|name, ty| narrowed.get(name) == Some(ty));
+    }
+    joined
+}
+
+/// The exception class with a name, which must be a builtin one.
+fn exn_class(name: &Ident) -> Result<Exn, Error> {
+    Exn::from_name(&name.to_string())
+        .ok_or_else(|| Error::new("unknown exception class").at(name.span()))
+}
+
 impl Check for Expn {
     type Info = Ty;
 
     fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error> {
-        self.0.check(defs, syms)
+        self.0.check(defs, syms).map_err(|e| e.at(self.span()))
     }
 }
 
@@ -296,26 +1652,125 @@ impl Check for Leaf {
                 e.check(defs, syms)?.expect_str()?;
                 Ty::Str
             }
-            Self::Int(_, e) => {
-                if matches!(e.check(defs, syms)?, Ty::Int | Ty::Bool) {
-                    // can convert to int
+            Self::Int(_, args) => match &mut args.iter_mut().collect::<Vec<_>>()[..] {
+                [e] => {
+                    if matches!(e.check(defs, syms)?, Ty::Int | Ty::Bool | Ty::Str) {
+                        // can convert to int
+                        Ty::Int
+                    } else {
+                        return Err("type error: expected int, bool or str".into());
+                    }
+                }
+                [e, base] => {
+                    // only a str has digits to read in another base
+                    e.check(defs, syms)?.expect_str()?;
+                    base.check(defs, syms)?.expect_int()?;
                     Ty::Int
-                } else {
-                    return Err("type error: expected int or bool");
                 }
-            }
+                _ => return Err("unexpected number of arguments".into()),
+            },
             Self::Str(_, e) => {
                 // can convert anything to a str
                 e.check(defs, syms)?;
                 Ty::Str
             }
-            Self::FuncCall { name, args } => todo!(), // TODO: later
+            Self::FuncCall { module, name, args } => {
+                let arrow = callee(module.as_prefix(), name, defs, syms)?;
+                arrow.check_call(args.iter_mut().map(Box::as_mut), defs, syms)?;
+                arrow
+                    .return_type
+                    .ok_or("function does not return a value")?
+            }
             Self::Nmbr(_) => Ty::Int,
             Self::Strg(_) => Ty::Str,
+            Self::FStrg(fstrg) => {
+                for part in &mut fstrg.parts {
+                    if let FPart::Expn(expn, spec) = part {
+                        // anything can be formatted, but only an int has a sign to pad after
+                        let ty = expn.check(defs, syms)?;
+                        if spec.zero && ty != Ty::Int {
+                            return Err(Error::new("zero padding needs an int").at(expn.span()));
+                        }
+                    }
+                }
+                Ty::Str
+            }
             Self::Bool(_) => Ty::Bool,
             Self::Unit(_) => Ty::Unit,
-            Self::Name(name) => syms.get_or(name)?,
+            Self::SelfRef(token) => syms
+                .get_or(&Ident::new("self", token.span))
+                .map_err(|_| Error::new("`self` is only defined in a method").at(token.span))?,
+            Self::Lambda(lambda) => {
+                for param in lambda.params() {
+                    defs.check_type(&param.ty)?;
+                }
+                let (mut inner_defs, mut locals) = nested(defs, syms, lambda.body.span());
+                for param in lambda.params() {
+                    locals.set(param.ident.clone(), &param.ty);
+                }
+                let ret = lambda.body.check(&mut inner_defs, &mut locals);
+                lambda.captures = unnest(defs, syms, (inner_defs, locals));
+                let params = lambda.params().map(|param| (&param.ty).into()).collect();
+                Ty::Func(params, Box::new(ret?))
+            }
+            Self::Name(name) => match syms.get_or(name) {
+                Ok(ty) => ty,
+                // a function's name is a value too
+                Err(e) => defs.get_or(name).map(Ty::from).map_err(|_| e)?,
+            },
+            Self::List(items) => {
+                // an empty list takes its type from where it goes
+                let mut ty = Ty::Never;
+                for item in items.iter_mut() {
+                    // items that are None make a list of optional values
+                    ty = ty.join(item.check(defs, syms)?).ok_or("mismatched types")?;
+                }
+                Ty::List(Box::new(ty))
+            }
             Self::Expn(e) => e.check(defs, syms)?,
         })
     }
 }
+
+impl Check for Indx {
+    type Info = Ty;
+
+    fn check(&mut self, defs: &mut DefTypes, syms: &mut SymTab) -> Result<Self::Info, Error> {
+        resolve_receiver(self, syms);
+        let ty = self.leaf.check(defs, syms)?;
+        check_postfixes(ty, self.postfixes.iter_mut(), defs, syms)
+    }
+}
+
+/// The type of a value of the given type once the postfixes are applied to it in turn.
+fn check_postfixes<'a>(
+    mut ty: Ty,
+    postfixes: impl IntoIterator<Item = &'a mut Postfix>,
+    defs: &mut DefTypes,
+    syms: &mut SymTab,
+) -> Result<Ty, Error> {
+    for postfix in postfixes {
+        ty = match postfix {
+            Postfix::Index(index) => {
+                index.check(defs, syms)?.expect_int()?;
+                match ty {
+                    Ty::List(item) => *item,
+                    Ty::Str => Ty::Str,
+                    other => return Err(other.expected("list or str")),
+                }
+            }
+            Postfix::Call(args) => {
+                let arrow = ty.arrow()?;
+                arrow.check_call(args.iter_mut().map(Box::as_mut), defs, syms)?;
+                arrow
+                    .return_type
+                    .ok_or("function does not return a value")?
+            }
+            Postfix::Field(_, name) => match ty {
+                Ty::Named(class) => defs.member(&class, name)?,
+                other => return Err(other.expected("object")),
+            },
+        };
+    }
+    Ok(ty)
+}
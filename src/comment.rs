@@ -0,0 +1,99 @@
+use std::ops::Range;
+
+/// A comment in the source text, which the parser otherwise throws away.
+///
+/// Python-style `#` comments run to the end of the line. Rust's lexer, which the parser is
+/// built on, also happens to accept `//` and `/* */` comments, so those are found here too.
+pub struct Comment {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub text: String,
+    /// Whether code precedes the comment on its line.
+    pub trailing: bool,
+    bytes: Range<usize>,
+}
+
+/// Find every comment in the source, in order.
+pub fn comments(source: &str) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut code_on_line = false;
+
+    while let Some((start, c)) = chars.next() {
+        let end = match c {
+            '\n' => {
+                line += 1;
+                line_start = start + 1;
+                code_on_line = false;
+                continue;
+            }
+            '"' => {
+                code_on_line = true;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\n' => {
+                            line += 1;
+                            line_start = i + 1;
+                        }
+                        _ => (),
+                    }
+                }
+                continue;
+            }
+            '#' => source[start..]
+                .find('\n')
+                .map_or(source.len(), |n| start + n),
+            '/' if matches!(chars.peek(), Some((_, '/'))) => source[start..]
+                .find('\n')
+                .map_or(source.len(), |n| start + n),
+            '/' if matches!(chars.peek(), Some((_, '*'))) => source[start + 2..]
+                .find("*/")
+                .map_or(source.len(), |n| start + n + 4),
+            c => {
+                code_on_line |= !c.is_whitespace();
+                continue;
+            }
+        };
+
+        let text = &source[start..end];
+        let newlines = text.matches('\n').count();
+        comments.push(Comment {
+            line,
+            column: source[line_start..start].chars().count(),
+            end_line: line + newlines,
+            text: text.trim_end().to_string(),
+            trailing: code_on_line,
+            bytes: start..end,
+        });
+        if let Some(n) = text.rfind('\n') {
+            line += newlines;
+            line_start = start + n + 1;
+        }
+        while chars.next_if(|&(i, _)| i < end).is_some() {}
+    }
+
+    comments
+}
+
+/// Blank out the `#` comments in the source, which Rust's lexer can't handle.
+///
+/// Each character of a comment becomes a space, so that every line and column the parser
+/// reports still matches the original source.
+pub fn strip(source: &str) -> String {
+    let mut stripped = source.to_string();
+    // go backwards so that earlier byte ranges stay put
+    for comment in comments(source).into_iter().rev() {
+        if comment.text.starts_with('#') {
+            let blank = " ".repeat(source[comment.bytes.clone()].chars().count());
+            stripped.replace_range(comment.bytes, &blank);
+        }
+    }
+    stripped
+}
@@ -0,0 +1,202 @@
+use std::{collections::BTreeSet, io::Write, path::Path};
+
+use parsel::Spanned;
+
+use crate::ast::{Expn, Stmt};
+use crate::comment;
+use crate::eval::{Context, Error, ErrorKind, Eval, Hook, Io};
+
+const HELP: &str = "\
+commands:
+  break [FILE:]LINE, b   stop whenever the statement on LINE is about to run, in the
+                         program's file or else in FILE, such as an imported module
+  delete [FILE:]LINE, d  remove the breakpoint on LINE of FILE
+  continue, c            run until the next breakpoint
+  step, s                run one statement, stepping into function calls
+  next, n                run one statement, stepping over function calls
+  finish, f              run until the current function returns
+  print EXPN, p EXPN     evaluate an expression in the current frame
+  locals, l              show the variables in the current frame
+  backtrace, bt          show the function calls in progress
+  quit, q                stop the program";
+
+/// When to stop next.
+#[derive(Clone, Copy)]
+enum Mode {
+    Continue,
+    Step,
+    /// Stop at a statement in a frame no deeper than this.
+    Next(usize),
+    /// Stop at a statement in a frame shallower than this.
+    Finish(usize),
+}
+
+/// A line of source, in the program's own file or else in the module it was imported as.
+type Place = (Option<String>, usize);
+
+/// An interactive debugger, which pauses before statements and takes commands.
+///
+/// It reads its commands from the same [`Io`] that programs read input from, so that the two
+/// can share one stream, such as the process's stdin.
+pub struct Debugger<I, W> {
+    input: I,
+    out: W,
+    /// The name of the program's file, without its extension, like the names of modules.
+    name: String,
+    lines: Vec<String>,
+    breakpoints: BTreeSet<Place>,
+    mode: Mode,
+}
+
+impl<I: Io, W: Write> Debugger<I, W> {
+    /// A debugger for the source of the program at the given path, which stops before the first
+    /// statement.
+    pub fn new(path: &str, source: &str, input: I, out: W) -> Self {
+        Self {
+            input,
+            out,
+            name: stem(path),
+            lines: source.lines().map(str::to_string).collect(),
+            breakpoints: BTreeSet::new(),
+            mode: Mode::Step,
+        }
+    }
+
+    /// The place a `break` or `delete` command names, like `12` in the program's own file or
+    /// `geometry:3` in an imported module.
+    fn place(&self, arg: &str) -> Option<Place> {
+        let (file, line) = match arg.rsplit_once(':') {
+            Some((file, line)) => (Some(file), line),
+            None => (None, arg),
+        };
+        let line = line.parse().ok()?;
+        let module = file.map(stem).filter(|name| *name != self.name);
+        Some((module, line))
+    }
+
+    /// Show a place the way commands name it.
+    fn show(&self, (module, line): &Place) -> String {
+        format!("{}:{line}", module.as_deref().unwrap_or(&self.name))
+    }
+
+    /// Take commands until one of them resumes the program.
+    fn pause(&mut self, line: usize, ctx: &mut Context) -> std::io::Result<Result<(), Error>> {
+        let text = match ctx.module() {
+            None => self.lines.get(line - 1).map(String::as_str),
+            Some(module) => ctx
+                .modules()
+                .get(module)
+                .and_then(|module| module.source.lines().nth(line - 1)),
+        };
+        let place = self.show(&(ctx.module().map(stem), line));
+        writeln!(self.out, "{place}: {}", text.unwrap_or("").trim())?;
+
+        loop {
+            write!(self.out, "(dwislpy) ")?;
+            self.out.flush()?;
+            let Some(command) = self.input.read_line()? else {
+                // nobody is left to give commands, so let the program finish
+                self.breakpoints.clear();
+                self.mode = Mode::Continue;
+                return Ok(Ok(()));
+            };
+            let (command, arg) = command
+                .trim()
+                .split_once(' ')
+                .map_or((command.trim(), ""), |(command, arg)| (command, arg.trim()));
+
+            let depth = ctx.frames().len();
+            match command {
+                "break" | "b" | "delete" | "d" => match self.place(arg) {
+                    Some(place) if command.starts_with('b') => {
+                        writeln!(self.out, "breakpoint at {}", self.show(&place))?;
+                        self.breakpoints.insert(place);
+                    }
+                    Some(place) => {
+                        writeln!(self.out, "removed breakpoint at {}", self.show(&place))?;
+                        self.breakpoints.remove(&place);
+                    }
+                    None => writeln!(self.out, "expected a line number")?,
+                },
+                "continue" | "c" => {
+                    self.mode = Mode::Continue;
+                    return Ok(Ok(()));
+                }
+                "step" | "s" => {
+                    self.mode = Mode::Step;
+                    return Ok(Ok(()));
+                }
+                "next" | "n" => {
+                    self.mode = Mode::Next(depth);
+                    return Ok(Ok(()));
+                }
+                "finish" | "f" => {
+                    self.mode = Mode::Finish(depth);
+                    return Ok(Ok(()));
+                }
+                "print" | "p" => match comment::strip(arg).parse::<Expn>() {
+                    Ok(mut expn) => match expn.eval(ctx) {
+                        Ok(value) => writeln!(self.out, "{value}")?,
+                        Err(e) => writeln!(self.out, "error: {e}")?,
+                    },
+                    Err(_) => writeln!(self.out, "error: could not parse expression")?,
+                },
+                "locals" | "l" => {
+                    let vars = ctx.vars();
+                    let mut vars: Vec<_> = vars.iter().collect();
+                    vars.sort_by_key(|(name, _)| name.to_string());
+                    for (name, value) in vars {
+                        writeln!(self.out, "{name} = {value}")?;
+                    }
+                }
+                "backtrace" | "bt" => {
+                    // each frame is paused where it called the one inside it
+                    let mut line = line;
+                    for frame in ctx.frames().iter().rev() {
+                        writeln!(self.out, "  in {}, line {line}", frame.name)?;
+                        line = frame.name.span().start().line;
+                    }
+                    writeln!(self.out, "  in <main>, line {line}")?;
+                }
+                "quit" | "q" => {
+                    return Ok(Err(Error {
+                        kind: ErrorKind::Stopped,
+                        ..Error::new("stopped by the debugger")
+                    }))
+                }
+                _ => writeln!(self.out, "{HELP}")?,
+            }
+        }
+    }
+}
+
+/// The name of a module or program from the path of its file, which is what commands and
+/// places call it by.
+fn stem(path: &str) -> String {
+    let name = Path::new(path).file_stem().unwrap_or_default();
+    name.to_string_lossy().into_owned()
+}
+
+impl<I: Io, W: Write> Hook for Debugger<I, W> {
+    fn before(&mut self, stmt: &Stmt, ctx: &mut Context) -> Result<(), Error> {
+        let line = stmt.span().start().line;
+        let depth = ctx.frames().len();
+        let place = (ctx.module().map(stem), line);
+        let stop = self.breakpoints.contains(&place)
+            || match self.mode {
+                Mode::Continue => false,
+                Mode::Step => true,
+                Mode::Next(at) => depth <= at,
+                Mode::Finish(at) => depth < at,
+            };
+        if !stop {
+            return Ok(());
+        }
+        self.pause(line, ctx).unwrap_or_else(|_| {
+            Err(Error {
+                kind: ErrorKind::Stopped,
+                ..Error::new("debugger i/o failed")
+            })
+        })
+    }
+}
@@ -1,14 +1,241 @@
-use std::{collections::HashMap, io::Write};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use parsel::{
     ast::{LeftAssoc, RightAssoc},
     syn::Ident,
-    Spanned,
+    Span, Spanned,
 };
 
 use crate::ast::*;
+use crate::check::{ArrowType, DefTypes};
+use crate::module::Modules;
+use crate::stdlib;
+
+/// Something that went wrong while checking or running a program.
+#[derive(Debug, Clone)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub msg: Cow<'static, str>,
+    /// Where in the source the problem is, if known.
+    pub span: Option<Span>,
+    /// The module whose source the span is in, or nothing for the main program.
+    pub module: Option<String>,
+    /// The calls and imports that were in progress, innermost first.
+    pub trace: Vec<Call>,
+}
+
+/// A function call or import that was in progress when something went wrong.
+#[derive(Debug, Clone)]
+pub struct Call {
+    /// The function's name as written at the call site, or the imported module's, which
+    /// locates it.
+    pub site: Ident,
+    /// The module whose source the site is in, or nothing for the main program.
+    pub module: Option<String>,
+    /// What was running inside it, as Python names it.
+    pub callee: String,
+}
+
+impl From<&'static str> for Error {
+    fn from(msg: &'static str) -> Self {
+        Self::new(msg)
+    }
+}
+
+impl From<String> for Error {
+    fn from(msg: String) -> Self {
+        Self {
+            msg: msg.into(),
+            ..Self::new("")
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl Error {
+    pub const fn new(msg: &'static str) -> Self {
+        Self::raise(Exn::RuntimeError, msg)
+    }
+
+    /// An `OverflowError`, for arithmetic on ints whose result doesn't fit.
+    pub const fn overflow() -> Self {
+        Self::raise(Exn::OverflowError, "int too large")
+    }
+
+    /// An exception of the given class, which the program can catch.
+    pub const fn raise(exn: Exn, msg: &'static str) -> Self {
+        Self {
+            kind: ErrorKind::Failed(exn),
+            msg: Cow::Borrowed(msg),
+            span: None,
+            module: None,
+            trace: Vec::new(),
+        }
+    }
+
+    /// Locate the error at the given span, unless it has already been located more precisely.
+    pub fn at(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    /// Describe where the error happened like Python does, from the outermost call in, given
+    /// the main program's path and source and the modules it imported.
+    pub fn traceback(&self, path: &str, source: &str, modules: &Modules) -> String {
+        let mut out = String::from("Traceback (most recent call last):\n");
+        let callers = std::iter::once("<module>")
+            .chain(self.trace.iter().rev().map(|call| call.callee.as_str()));
+        let places = self
+            .trace
+            .iter()
+            .rev()
+            .map(|call| (call.module.as_deref(), Some(call.site.span())))
+            .chain([(self.module.as_deref(), self.span)]);
+        let mut last = None;
+        let mut repeats = 0;
+        for (caller, (module, span)) in callers.zip(places) {
+            // calls from the host aren't anywhere in the source
+            let Some(span) = span.filter(|span| span.start() != span.end()) else {
+                continue;
+            };
+            let line = span.start().line;
+            let (path, source) = match module.map(|name| modules.get(name)) {
+                None => (path.to_string(), source),
+                Some(Some(module)) => (module.path.display().to_string(), &module.source[..]),
+                Some(None) => continue,
+            };
+            // runaway recursion repeats the same entry, so only show it a few times
+            let entry = (caller, path, line);
+            if last.as_ref() == Some(&entry) {
+                repeats += 1;
+                if repeats >= 3 {
+                    continue;
+                }
+            } else {
+                push_repeats(&mut out, repeats);
+                repeats = 0;
+            }
+            out.push_str(&format!(
+                "  File \"{}\", line {line}, in {caller}\n",
+                entry.1
+            ));
+            if let Some(text) = source.lines().nth(line - 1) {
+                out.push_str(&format!("    {}\n", text.trim()));
+            }
+            last = Some(entry);
+        }
+        push_repeats(&mut out, repeats);
+        out
+    }
+}
+
+/// Why a program stopped, which decides the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// It was wrong, or did something wrong, which it could have caught as an exception.
+    Failed(Exn),
+    /// Its host stopped it, like the debugger does when asked to quit.
+    Stopped,
+    /// It ran more statements than it was allowed to.
+    OutOfSteps,
+    /// It ran for longer than it was allowed to.
+    OutOfTime,
+}
+
+impl ErrorKind {
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Failed(_) | Self::Stopped => 1,
+            Self::OutOfSteps => 3,
+            Self::OutOfTime => 4,
+        }
+    }
+}
+
+macro_rules! exceptions {
+    ($($(#[$doc:meta])* $exn:ident,)*) => {
+        /// The classes of exception that programs can raise and catch, named as in Python.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Exn {
+            $($(#[$doc])* $exn,)*
+        }
+
+        impl Exn {
+            /// Every class, in the order they are declared.
+            pub const ALL: &[Self] = &[$(Self::$exn),*];
+
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(Self::$exn => stringify!($exn),)*
+                }
+            }
+        }
+    };
+}
+
+exceptions! {
+    /// Any exception at all, which only `except` uses.
+    Exception,
+    /// A failure that fits none of the other classes.
+    RuntimeError,
+    /// A value of the right type, but which makes no sense, like `int("ten")`.
+    ValueError,
+    /// A value of the wrong type, which the checker usually rules out.
+    TypeError,
+    ZeroDivisionError,
+    /// Arithmetic whose result is too big for an int to hold.
+    OverflowError,
+    IndexError,
+    /// A name that isn't defined.
+    NameError,
+    RecursionError,
+    /// Input or output that failed.
+    OSError,
+    /// An `assert` whose condition didn't hold.
+    AssertionError,
+    /// A field that an object doesn't have.
+    AttributeError,
+}
+
+impl Exn {
+    /// The class with a name, if there is one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|exn| exn.name() == name)
+    }
+
+    /// Whether an `except` of this class catches an exception of the other.
+    pub fn catches(self, other: Self) -> bool {
+        self == Self::Exception || self == other
+    }
+}
 
-pub type Error = &'static str;
+impl std::fmt::Display for Exn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+fn push_repeats(out: &mut String, repeats: usize) {
+    if repeats >= 3 {
+        out.push_str(&format!(
+            "  [Previous line repeated {} more times]\n",
+            repeats - 2
+        ));
+    }
+}
 
 #[derive(Debug, Clone)]
 #[must_use]
@@ -17,11 +244,155 @@ pub enum Value {
     Int(i128),
     Str(String),
     Bool(bool),
+    /// A list, which can't change, so copies of it share its items.
+    List(Rc<Vec<Value>>),
     Func {
-        captures: Context,
+        /// The variables of enclosing functions it uses, shared with them.
+        captures: Cells,
         params: Vec<Ident>,
-        rule: Nest,
+        rule: Body,
+        /// The module it was defined in, whose globals it sees, or nothing for the main program.
+        module: Option<String>,
     },
+    Native(Native),
+    /// A class, which makes an object when called.
+    Class(Rc<ClassInfo>),
+    /// An object, which every value holding it shares, so that assigning to one of its fields
+    /// changes it for all of them.
+    Object(Rc<RefCell<Object>>),
+    /// A variant of an enum with fields, which makes a value of the enum when called.
+    Variant(Rc<VariantInfo>),
+    /// A value of an enum: the variant it is, with the values of its fields.
+    Enum(Rc<VariantInfo>, Vec<Value>),
+}
+
+/// What a function runs when it is called.
+#[derive(Debug, Clone)]
+pub enum Body {
+    /// The nest of a `def`.
+    Nest(Nest),
+    /// The expression of a `lambda`, whose value it returns.
+    Expn(Box<Expn>),
+}
+
+impl Eval for Body {
+    type Output = Option<Value>;
+
+    fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
+        match self {
+            Self::Nest(nest) => nest.eval(ctx),
+            // there is no statement to say where it failed, so the body does
+            Self::Expn(expn) => expn.eval(ctx).map(Some).map_err(|e| e.at(expn.span())),
+        }
+    }
+}
+
+impl Spanned for Body {
+    fn span(&self) -> Span {
+        match self {
+            Self::Nest(nest) => nest.span(),
+            Self::Expn(expn) => expn.span(),
+        }
+    }
+}
+
+/// The Rust code of a native function, which gets its arguments and the context it runs in.
+type NativeFn = dyn Fn(Vec<Value>, &mut Context) -> Result<Value, Error>;
+
+/// A function written in Rust by the host, rather than in DWISPY.
+#[derive(Clone)]
+pub struct Native {
+    /// Its signature, which the checker holds calls to.
+    pub arrow: ArrowType,
+    func: Rc<NativeFn>,
+}
+
+impl std::fmt::Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Native({})", self.arrow)
+    }
+}
+
+/// A class as the program runs.
+#[derive(Debug)]
+pub struct ClassInfo {
+    pub name: String,
+    /// The names of its fields, in the order its constructor takes them.
+    pub fields: Vec<Ident>,
+    /// Its methods, as functions of the parameters after `self`, which binding to an object
+    /// gives as a captured variable.
+    pub methods: HashMap<Ident, Value>,
+}
+
+/// A variant of an enum as the program runs.
+#[derive(Debug)]
+pub struct VariantInfo {
+    pub name: String,
+    /// How many fields it has, which its constructor takes values for.
+    pub arity: usize,
+}
+
+/// An object of a class, with a value for each of the class's fields, in the same order.
+#[derive(Debug)]
+pub struct Object {
+    pub class: Rc<ClassInfo>,
+    pub fields: Vec<Value>,
+}
+
+impl Object {
+    fn no_member(&self, name: &Ident) -> Error {
+        let msg = format!("`{}` has no field or method `{name}`", self.class.name);
+        Error {
+            msg: msg.into(),
+            ..Error::raise(Exn::AttributeError, "")
+        }
+        .at(name.span())
+    }
+
+    fn index(&self, name: &Ident) -> Result<usize, Error> {
+        let index = self.class.fields.iter().position(|field| field == name);
+        index.ok_or_else(|| self.no_member(name))
+    }
+
+    /// The value of a field of an object, or one of its methods, bound to the object so that
+    /// calling it only takes the other arguments.
+    ///
+    /// # Errors
+    /// If the object's class has no such field or method.
+    ///
+    pub fn get(this: &Rc<RefCell<Self>>, name: &Ident) -> Result<Value, Error> {
+        let object = this.borrow();
+        if let Some(index) = object.class.fields.iter().position(|field| field == name) {
+            return Ok(object.fields[index].clone());
+        }
+        let Some(Value::Func {
+            params,
+            rule,
+            module,
+            ..
+        }) = object.class.methods.get(name)
+        else {
+            return Err(object.no_member(name));
+        };
+        let this = Rc::new(RefCell::new(Some(Value::Object(this.clone()))));
+        Ok(Value::Func {
+            captures: Cells::from([(Ident::new("self", Span::call_site()), this)]),
+            params: params.clone(),
+            rule: rule.clone(),
+            module: module.clone(),
+        })
+    }
+
+    /// Assign to a field.
+    ///
+    /// # Errors
+    /// If the object's class has no such field.
+    ///
+    pub fn set(&mut self, name: &Ident, value: Value) -> Result<(), Error> {
+        let index = self.index(name)?;
+        self.fields[index] = value;
+        Ok(())
+    }
 }
 
 impl std::fmt::Display for Value {
@@ -31,11 +402,77 @@ impl std::fmt::Display for Value {
             Self::Int(n) => write!(f, "{n}"),
             Self::Str(s) => write!(f, "{s}"),
             Self::Bool(b) => write!(f, "{b}"),
+            Self::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_inner(f, item)?;
+                }
+                write!(f, "]")
+            }
             Self::Func { .. } => write!(f, "function object"),
+            Self::Native(_) => write!(f, "built-in function"),
+            Self::Class(class) => write!(f, "class {}", class.name),
+            // like a Python dataclass
+            Self::Object(object) => {
+                let object = object.borrow();
+                write!(f, "{}(", object.class.name)?;
+                let fields = object.class.fields.iter().zip(&object.fields);
+                for (i, (name, value)) in fields.enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}=")?;
+                    write_inner(f, value)?;
+                }
+                write!(f, ")")
+            }
+            Self::Variant(variant) => write!(f, "variant {}", variant.name),
+            Self::Enum(variant, fields) if fields.is_empty() => write!(f, "{}", variant.name),
+            Self::Enum(variant, fields) => {
+                write!(f, "{}(", variant.name)?;
+                for (i, value) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write_inner(f, value)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
 
+/// Write a value inside another, where, like in Python, strs are shown as they would be written.
+fn write_inner(f: &mut std::fmt::Formatter<'_>, value: &Value) -> std::fmt::Result {
+    match value {
+        Value::Str(s) => write_quoted(f, s),
+        value => write!(f, "{value}"),
+    }
+}
+
+/// Write a str in quotes, the way Python does.
+fn write_quoted(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    let quote = if s.contains('\'') && !s.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    write!(f, "{quote}")?;
+    for c in s.chars() {
+        match c {
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            c if c == quote => write!(f, "\\{c}")?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "{quote}")
+}
+
 impl From<i128> for Value {
     fn from(n: i128) -> Self {
         Self::Int(n)
@@ -54,18 +491,82 @@ impl From<bool> for Value {
     }
 }
 
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::Str(s.to_string())
+    }
+}
+
 impl From<()> for Value {
     fn from(_: ()) -> Self {
         Self::Unit
     }
 }
 
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        Self::List(Rc::new(items.into_iter().map(Into::into).collect()))
+    }
+}
+
+impl TryFrom<Value> for i128 {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        value.expect_int()
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        value.expect_bool()
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        if let Value::Str(s) = value {
+            Ok(s)
+        } else {
+            Err(Error::raise(Exn::TypeError, "type error: expected str"))
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<Value> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        if let Value::List(items) = value {
+            Ok(Rc::unwrap_or_clone(items))
+        } else {
+            Err(Error::raise(Exn::TypeError, "type error: expected list"))
+        }
+    }
+}
+
+impl TryFrom<Value> for () {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Error> {
+        if let Value::Unit = value {
+            Ok(())
+        } else {
+            Err(Error::raise(Exn::TypeError, "type error: expected None"))
+        }
+    }
+}
+
 impl Value {
     pub const fn expect_int(&self) -> Result<i128, Error> {
         if let &Self::Int(n) = self {
             Ok(n)
         } else {
-            Err("type error: expected int")
+            Err(Error::raise(Exn::TypeError, "type error: expected int"))
         }
     }
 
@@ -73,52 +574,674 @@ impl Value {
         if let &Self::Bool(b) = self {
             Ok(b)
         } else {
-            Err("type error: expected bool")
+            Err(Error::raise(Exn::TypeError, "type error: expected bool"))
         }
     }
 
-    pub fn expect_func(&self) -> Result<(Context, Vec<Ident>, Nest), Error> {
+    pub fn expect_str(&self) -> Result<&str, Error> {
+        if let Self::Str(s) = self {
+            Ok(s)
+        } else {
+            Err(Error::raise(Exn::TypeError, "type error: expected str"))
+        }
+    }
+
+    /// The items of a list, or the characters of a str.
+    pub fn expect_items(self) -> Result<Vec<Self>, Error> {
+        match self {
+            Self::List(items) => Ok(Rc::unwrap_or_clone(items)),
+            Self::Str(s) => Ok(s.chars().map(|c| c.to_string().into()).collect()),
+            _ => Err(Error::raise(
+                Exn::TypeError,
+                "type error: expected list or str",
+            )),
+        }
+    }
+
+    /// This value as a str, the way `str` and `print` show it, which for an object whose class
+    /// has a `__str__` method is what that returns.
+    ///
+    /// # Errors
+    /// If `__str__` fails.
+    ///
+    pub fn to_str(&self, ctx: &mut Context) -> Result<String, Error> {
+        let name = Ident::new("__str__", Span::call_site());
+        match self {
+            Self::Object(object) if object.borrow().class.methods.contains_key(&name) => {
+                let method = Object::get(object, &name)?;
+                let shown = method.try_call_with(&name, Vec::new(), ctx)?;
+                Ok(shown.unwrap_or(Self::Unit).expect_str()?.to_string())
+            }
+            _ => Ok(self.to_string()),
+        }
+    }
+
+    pub fn expect_object(&self) -> Result<&Rc<RefCell<Object>>, Error> {
+        if let Self::Object(object) = self {
+            Ok(object)
+        } else {
+            Err(Error::raise(Exn::TypeError, "type error: expected object"))
+        }
+    }
+
+    pub fn expect_func(&self) -> Result<(Cells, Vec<Ident>, Body, Option<String>), Error> {
         if let Self::Func {
             captures,
             params,
             rule,
+            module,
+            ..
         } = self
         {
-            Ok((captures.clone(), params.clone(), rule.clone()))
+            Ok((
+                captures.clone(),
+                params.clone(),
+                rule.clone(),
+                module.clone(),
+            ))
         } else {
-            Err("type error: expected function")
+            Err(Error::raise(
+                Exn::TypeError,
+                "type error: expected function",
+            ))
         }
     }
 
-    pub fn try_call_with(&self, args: Vec<Self>) -> Result<Option<Self>, Error> {
-        let (mut call_ctx, params, mut rule) = self.expect_func()?;
+    /// Call this function in a new frame, named as it was at the call site.
+    pub fn try_call_with(
+        &self,
+        name: &Ident,
+        args: Vec<Self>,
+        ctx: &mut Context,
+    ) -> Result<Option<Self>, Error> {
+        if let Self::Native(native) = self {
+            let arity = native.arrow.params.len();
+            if !(arity - native.arrow.defaults..=arity).contains(&args.len()) {
+                return Err(Error::raise(
+                    Exn::TypeError,
+                    "unexpected number of arguments",
+                ));
+            }
+            return (native.func)(args, ctx).map(Some);
+        }
+        if let Self::Class(class) = self {
+            if args.len() != class.fields.len() {
+                return Err(Error::raise(
+                    Exn::TypeError,
+                    "unexpected number of arguments",
+                ));
+            }
+            let object = Object {
+                class: class.clone(),
+                fields: args,
+            };
+            return Ok(Some(Self::Object(Rc::new(RefCell::new(object)))));
+        }
+        if let Self::Variant(variant) = self {
+            if args.len() != variant.arity {
+                return Err(Error::raise(
+                    Exn::TypeError,
+                    "unexpected number of arguments",
+                ));
+            }
+            return Ok(Some(Self::Enum(variant.clone(), args)));
+        }
+
+        let (mut locals, params, mut rule, module) = self.expect_func()?;
         if args.len() != params.len() {
-            return Err("unexpected number of arguments");
+            return Err(Error::raise(
+                Exn::TypeError,
+                "unexpected number of arguments",
+            ));
+        }
+        if ctx.frames.len() >= ctx.max_depth {
+            return Err(Error::raise(
+                Exn::RecursionError,
+                "maximum recursion depth exceeded",
+            ));
         }
         for (param, arg) in params.into_iter().zip(args) {
-            call_ctx.set(param, arg);
+            locals.insert(param, Rc::new(RefCell::new(Some(arg))));
         }
 
+        ctx.frames.push(Frame {
+            name: name.clone(),
+            module,
+            locals,
+            globals: Vec::new(),
+        });
         // semantically, if a function does not return a value in an expn context, we
         // assume it returned None
-        rule.eval(&mut call_ctx)
+        let result = rule.eval(ctx);
+        let frame = ctx.frames.pop().expect("the call's frame is still there");
+        release(frame.locals);
+        result.map_err(|mut e| {
+            // the innermost call is where the problem is
+            if e.trace.is_empty() {
+                e.module = frame.module;
+            }
+            e.trace.push(Call {
+                site: name.clone(),
+                module: ctx.module().map(str::to_string),
+                callee: name.to_string(),
+            });
+            e
+        })
     }
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct Context(HashMap<Ident, Value>);
+pub type Vars = HashMap<Ident, Value>;
+
+/// A variable which functions can share, so that each sees what the others assign to it. It is
+/// empty until it is first assigned.
+pub type Cell = Rc<RefCell<Option<Value>>>;
 
-impl Context {
-    fn get(&mut self, name: &Ident) -> Option<&mut Value> {
-        self.0.get_mut(name)
+/// The shared variables of a function, by name.
+pub type Cells = HashMap<Ident, Cell>;
+
+/// Empty the variables of a call that has returned which only the functions defined in it still
+/// hold. A function that calls itself, or two that call each other, capture each other's
+/// variables, and would otherwise keep each other alive forever. Anything held from outside the
+/// call, like a function it returned, keeps the variables its functions capture.
+fn release(locals: Cells) {
+    let holds_func = |cell: &Cell| matches!(&*cell.borrow(), Some(Value::Func { .. }));
+    if !locals.values().any(holds_func) {
+        return;
     }
+    let funcs = |cell: &Cell| match &*cell.borrow() {
+        Some(Value::Func { captures, .. }) => captures.values().map(Rc::as_ptr).collect(),
+        _ => Vec::new(),
+    };
+    let cells: HashMap<_, _> = locals
+        .into_values()
+        .map(|cell| (Rc::as_ptr(&cell), cell))
+        .collect();
+    let captures: HashMap<_, Vec<_>> = cells.iter().map(|(&at, cell)| (at, funcs(cell))).collect();
 
-    fn get_or(&mut self, name: &Ident) -> Result<&mut Value, Error> {
-        self.get(name).ok_or("undefined variable")
+    // how many times the functions in these cells hold each of them
+    let mut held = HashMap::new();
+    for at in captures.values().flatten() {
+        *held.entry(*at).or_insert(0) += 1;
+    }
+    // so a cell held any more often than that and by this map is held from outside the call,
+    // and keeps alive what its function captures
+    let mut live: Vec<_> = cells
+        .iter()
+        .filter(|&(at, cell)| Rc::strong_count(cell) > 1 + held.get(at).copied().unwrap_or(0))
+        .map(|(&at, _)| at)
+        .collect();
+    let mut seen: HashSet<_> = live.iter().copied().collect();
+    while let Some(at) = live.pop() {
+        for &captured in captures.get(&at).into_iter().flatten() {
+            if cells.contains_key(&captured) && seen.insert(captured) {
+                live.push(captured);
+            }
+        }
+    }
+    for (at, cell) in &cells {
+        if !seen.contains(at) {
+            // dropped after the borrow ends, since it may hold the last of other cells
+            let value = cell.borrow_mut().take();
+            drop(value);
+        }
+    }
+}
+
+/// The variables of a function call in progress.
+pub struct Frame {
+    /// The function's name as written at the call site, which locates the call.
+    pub name: Ident,
+    /// The module the function was defined in, or nothing for the main program.
+    pub module: Option<String>,
+    /// Its own variables, along with those it captured from enclosing functions.
+    pub locals: Cells,
+    /// The names it has declared `global`, which assignments go to the globals for.
+    pub globals: Vec<Ident>,
+}
+
+/// Something that watches a program as it runs, like a debugger.
+pub trait Hook {
+    /// Called just before each statement runs.
+    ///
+    /// # Errors
+    /// To stop the program.
+    ///
+    fn before(&mut self, stmt: &Stmt, ctx: &mut Context) -> Result<(), Error>;
+}
+
+/// Where a program's input comes from and where its output goes.
+pub trait Io {
+    /// Show some output, exactly as given.
+    ///
+    /// # Errors
+    /// If the output can't be written.
+    ///
+    fn write(&mut self, text: &str) -> io::Result<()>;
+
+    /// Take a line of input, without its line ending, or nothing at the end of the input.
+    ///
+    /// # Errors
+    /// If the input can't be read.
+    ///
+    fn read_line(&mut self) -> io::Result<Option<String>>;
+}
+
+/// The process's own standard input and output.
+pub struct Stdio;
+
+impl Io for Stdio {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = io::stdout();
+        stdout.write_all(text.as_bytes())?;
+        // prompts don't end in a newline, but should show up before we wait for input
+        stdout.flush()
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        Streams {
+            input: io::stdin().lock(),
+            output: io::sink(),
+        }
+        .read_line()
+    }
+}
+
+/// Any pair of streams, like in-memory buffers or a script of inputs.
+pub struct Streams<R, W> {
+    pub input: R,
+    pub output: W,
+}
+
+impl<R: BufRead, W: Write> Io for Streams<R, W> {
+    fn write(&mut self, text: &str) -> io::Result<()> {
+        self.output.write_all(text.as_bytes())
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(Some(line))
+    }
+}
+
+/// How deeply functions can call each other when the CLI runs a program, as in Python, on a
+/// thread with [`stack_size`] for it.
+pub const MAX_DEPTH: usize = 1000;
+
+/// How deeply functions can call each other in a [`Context`] by default, which fits in the
+/// 8 MiB stack of a main thread even in a debug build.
+///
+/// Each call recurses through the evaluator, which takes up to about 100 KiB of native stack
+/// in a debug build. A host that wants deeper calls raises the limit with
+/// [`Context::with_max_depth`] and runs the program on a thread with [`stack_size`] for it.
+pub const HOST_DEPTH: usize = 50;
+
+/// How much native stack it takes to make calls this deep, generously.
+///
+/// Each call recurses through the evaluator, so this is far more than the main thread gets.
+pub const fn stack_size(max_depth: usize) -> usize {
+    const PER_CALL: usize = 128 * 1024;
+    (max_depth + 1).saturating_mul(PER_CALL)
+}
+
+/// The state of a running program.
+pub struct Context<'a> {
+    /// The functions the host defines, which the program's own globals can shadow.
+    builtins: Vars,
+    globals: Vars,
+    modules: Modules,
+    /// The module whose top-level code is running, or nothing for the main program.
+    module: Option<String>,
+    frames: Vec<Frame>,
+    hook: Option<Box<dyn Hook + 'a>>,
+    io: Box<dyn Io + 'a>,
+    max_depth: usize,
+    /// Whether `assert` statements run, or are skipped.
+    asserts: bool,
+    /// How many more statements may run, if that is limited.
+    steps_left: Option<u64>,
+    deadline: Option<Instant>,
+}
+
+impl Default for Context<'_> {
+    fn default() -> Self {
+        let mut ctx = Self {
+            builtins: Vars::default(),
+            globals: Vars::default(),
+            modules: Modules::default(),
+            module: None,
+            frames: Vec::new(),
+            hook: None,
+            io: Box::new(Stdio),
+            max_depth: HOST_DEPTH,
+            asserts: true,
+            steps_left: None,
+            deadline: None,
+        };
+        stdlib::install(&mut ctx).expect("the builtins' signatures are valid");
+        ctx
+    }
+}
+
+impl<'a> Context<'a> {
+    pub fn with_hook(hook: impl Hook + 'a) -> Self {
+        Self {
+            hook: Some(Box::new(hook)),
+            ..Self::default()
+        }
+    }
+
+    /// Send the program's output, and take its input, somewhere other than stdout and stdin.
+    pub fn with_io(self, io: impl Io + 'a) -> Self {
+        Self {
+            io: Box::new(io),
+            ..self
+        }
+    }
+
+    /// Look for imported modules in these directories, in order, after the importing module's
+    /// own directory.
+    pub fn with_search_path(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.modules.search_path.extend(dirs);
+        self
+    }
+
+    /// Run `assert` statements, or with `false`, skip them without evaluating anything.
+    pub fn with_asserts(self, asserts: bool) -> Self {
+        Self { asserts, ..self }
+    }
+
+    /// Limit how many function calls can be in progress at once.
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Limit how many statements and loop iterations the program may run, calls included.
+    pub fn with_max_steps(self, max_steps: u64) -> Self {
+        Self {
+            steps_left: Some(max_steps),
+            ..self
+        }
+    }
+
+    /// Limit how long the program may run, starting now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            deadline: Instant::now().checked_add(timeout),
+            ..self
+        }
+    }
+
+    /// Account for one more statement or loop iteration, or stop the program if it has run out
+    /// of steps or time.
+    pub(crate) fn step(&mut self) -> Result<(), Error> {
+        if let Some(steps) = &mut self.steps_left {
+            *steps = steps.checked_sub(1).ok_or(Error {
+                kind: ErrorKind::OutOfSteps,
+                ..Error::new("step limit exceeded")
+            })?;
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(Error {
+                kind: ErrorKind::OutOfTime,
+                ..Error::new("time limit exceeded")
+            });
+        }
+        Ok(())
+    }
+
+    /// The function calls in progress, innermost last.
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// The program's global variables, including its functions.
+    pub fn globals(&self) -> &Vars {
+        &self.globals
+    }
+
+    /// The modules that have been imported.
+    pub fn modules(&self) -> &Modules {
+        &self.modules
+    }
+
+    /// The module whose code is running, or nothing for the main program.
+    pub fn module(&self) -> Option<&str> {
+        let frame = self.frames.last();
+        frame.map_or(self.module.as_deref(), |frame| frame.module.as_deref())
+    }
+
+    /// Define a global function that runs Rust code, with the signature the checker should hold
+    /// calls to it to.
+    ///
+    /// # Errors
+    /// If the signature has more defaults than parameters.
+    ///
+    /// ```
+    /// use dwislpy::{check::{ArrowType, Ty}, Context};
+    ///
+    /// let mut ctx = Context::default();
+    /// let arrow = ArrowType { params: vec![Ty::Int], return_type: None, defaults: 2 };
+    /// let e = ctx.define("f", arrow, |_| Ok(().into())).unwrap_err();
+    /// assert_eq!(e.msg, "`f` has more defaults than parameters");
+    /// ```
+    pub fn define(
+        &mut self,
+        name: &str,
+        arrow: ArrowType,
+        func: impl Fn(Vec<Value>) -> Result<Value, Error> + 'static,
+    ) -> Result<(), Error> {
+        self.define_with_context(name, arrow, move |args, _| func(args))
+    }
+
+    /// Define a global function like [`Context::define`], whose Rust code also gets the context,
+    /// so that it can account for work that takes more than a step.
+    pub(crate) fn define_with_context(
+        &mut self,
+        name: &str,
+        arrow: ArrowType,
+        func: impl Fn(Vec<Value>, &mut Context) -> Result<Value, Error> + 'static,
+    ) -> Result<(), Error> {
+        arrow.check_defaults(name)?;
+        let func = Rc::new(func);
+        let name = Ident::new(name, Span::call_site());
+        self.builtins
+            .insert(name, Value::Native(Native { arrow, func }));
+        Ok(())
+    }
+
+    /// The signatures of the functions the host has defined, for checking programs against.
+    pub fn def_types(&self) -> DefTypes {
+        let mut defs = DefTypes::default();
+        for (name, value) in &self.builtins {
+            if let Value::Native(native) = value {
+                let declared = defs.declare(name.clone(), native.arrow.clone());
+                declared.expect("`define` checked the signature");
+            }
+        }
+        defs
+    }
+
+    /// The signatures that a program with these imports can call, loading and checking each
+    /// module the first time it is imported.
+    ///
+    /// # Errors
+    /// If a module can't be found or doesn't check, or the modules import each other.
+    ///
+    pub fn import_types<'i>(
+        &mut self,
+        imports: impl IntoIterator<Item = &'i Import>,
+    ) -> Result<DefTypes, Error> {
+        let builtins = self.def_types();
+        self.modules.import_types(imports, None, &builtins)
+    }
+
+    /// Run an imported module's top-level code, unless it already has, and bring in any names
+    /// the import asks for.
+    fn import(&mut self, import: &Import) -> Result<(), Error> {
+        let name = import.module();
+        let missing = || Error::from(format!("no module named `{name}`")).at(name.span());
+        let key = self
+            .modules
+            .resolve(self.module(), name)
+            .map(str::to_string);
+        let key = key.ok_or_else(missing)?;
+        let module = self
+            .modules
+            .get_mut(&key)
+            .filter(|module| module.prgm.is_some())
+            .ok_or_else(missing)?;
+        if !module.ran {
+            module.ran = true;
+            let mut prgm = module.prgm.clone().expect("module has checked");
+            let outer = self.module.replace(key.clone());
+            let result = prgm.eval(self);
+            self.module = outer;
+            result.map_err(|mut e| {
+                if e.trace.is_empty() {
+                    e.module = Some(key.clone());
+                }
+                e.trace.push(Call {
+                    site: name.clone(),
+                    module: self.module().map(str::to_string),
+                    callee: "<module>".to_string(),
+                });
+                e
+            })?;
+        }
+
+        if let Import::From { names, .. } = import {
+            for name in names.iter() {
+                let value = self.get_qualified_or(import.module(), name)?.clone();
+                self.set(name.clone(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Call a global function by name, returning what it returns, if anything.
+    ///
+    /// # Errors
+    /// If there is no such function, or it fails.
+    ///
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Option<Value>, Error> {
+        let ident = Ident::new(name, Span::call_site());
+        let func = self.get_or(&ident)?;
+        func.try_call_with(&ident, args, self)
+    }
+
+    /// The variables of the current function that a function defined here uses, to share
+    /// with it. Globals are looked up when it runs, so they are never captured.
+    ///
+    /// A name that hasn't been assigned yet, like a variable declared further down or a function
+    /// defined later in the block, gets an empty cell that its assignment will fill in. Reading it
+    /// before then is a `NameError`.
+    fn captures(&mut self, names: &Captures) -> Cells {
+        let Some(frame) = self.frames.last_mut() else {
+            return Cells::new();
+        };
+        let names = names.0.iter();
+        names
+            .map(|name| {
+                let cell = frame.locals.entry(name.clone());
+                let cell = cell.or_insert_with(|| Rc::new(RefCell::new(None)));
+                (name.clone(), cell.clone())
+            })
+            .collect()
+    }
+
+    /// Look up a global variable by name.
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.globals
+            .iter()
+            .find_map(|(ident, value)| (ident == name).then_some(value))
+    }
+
+    /// The variables of the current function, or the globals outside of one.
+    pub fn vars(&self) -> Vars {
+        match (self.frames.last(), self.module.as_deref()) {
+            (Some(frame), _) => frame
+                .locals
+                .iter()
+                .filter_map(|(name, cell)| Some((name.clone(), cell.borrow().clone()?)))
+                .collect(),
+            (None, Some(module)) => {
+                let module = self.modules.get(module).expect("module is loaded");
+                module.globals.clone()
+            }
+            (None, None) => self.globals.clone(),
+        }
+    }
+
+    /// The globals of the module whose code is running.
+    fn scope(&mut self) -> &mut Vars {
+        match self.module().map(str::to_string) {
+            Some(module) => {
+                let module = self.modules.get_mut(&module);
+                &mut module.expect("a running module is loaded").globals
+            }
+            None => &mut self.globals,
+        }
+    }
+
+    fn get(&mut self, name: &Ident) -> Option<Value> {
+        let frame = self.frames.last();
+        if let Some(cell) = frame.and_then(|frame| frame.locals.get(name)) {
+            // a variable that isn't assigned yet isn't there at all
+            return cell.borrow().clone();
+        }
+        // functions fall back on globals, which lets them call themselves and each other
+        match self.scope().get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.builtins.get(name).cloned(),
+        }
+    }
+
+    /// Look up a function from an imported module by name.
+    fn get_qualified_or(&mut self, module: &Ident, name: &Ident) -> Result<&mut Value, Error> {
+        let key = self
+            .modules
+            .resolve(self.module(), module)
+            .map(str::to_string);
+        key.and_then(|key| self.modules.get_mut(&key))
+            .and_then(|module| module.globals.get_mut(name))
+            .ok_or_else(|| Error::raise(Exn::NameError, "undefined function").at(name.span()))
+    }
+
+    /// The function a call names, which may come from a module.
+    fn callee(&mut self, module: Option<&Qual>, name: &Ident) -> Result<Value, Error> {
+        match module {
+            Some(qual) => self.get_qualified_or(&qual.module, name).cloned(),
+            None => self.get_or(name),
+        }
+    }
+
+    fn get_or(&mut self, name: &Ident) -> Result<Value, Error> {
+        self.get(name)
+            .ok_or_else(|| Error::raise(Exn::NameError, "undefined variable").at(name.span()))
     }
 
     fn set(&mut self, name: Ident, val: impl Into<Value>) {
-        self.0.insert(name, val.into());
+        let val = val.into();
+        if let Some(frame) = self.frames.last_mut() {
+            // a captured variable is assigned for every function that shares it
+            if let Some(cell) = frame.locals.get(&name) {
+                *cell.borrow_mut() = Some(val);
+                return;
+            }
+            if !frame.globals.contains(&name) {
+                frame.locals.insert(name, Rc::new(RefCell::new(Some(val))));
+                return;
+            }
+        }
+        self.scope().insert(name, val);
     }
 }
 
@@ -132,10 +1255,97 @@ impl Eval for Prgm {
     type Output = ();
 
     fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
+        self.load(ctx)?;
+        self.main.eval(ctx).map(|_| ())
+    }
+}
+
+impl Prgm {
+    /// Run the imports and define the functions, but not the main block.
+    pub fn load(&mut self, ctx: &mut Context) -> Result<(), Error> {
+        for import in self.imports.iter() {
+            ctx.import(import)?;
+        }
+        for enm in &mut self.enums {
+            enm.eval(ctx)?;
+        }
+        for class in &mut self.classes {
+            class.eval(ctx)?;
+        }
         for def in &mut self.defns {
             def.eval(ctx)?;
         }
-        self.main.eval(ctx).map(|_| ())
+        Ok(())
+    }
+}
+
+impl Eval for Enum {
+    type Output = ();
+
+    fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
+        for variant in self.variants.iter() {
+            let info = Rc::new(VariantInfo {
+                name: variant.name.to_string(),
+                arity: variant.fields().count(),
+            });
+            // a variant without fields is the only value it makes
+            let value = if variant.fields.as_prefix().is_some() {
+                Value::Variant(info)
+            } else {
+                Value::Enum(info, Vec::new())
+            };
+            ctx.set(variant.name.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+impl Pattern {
+    /// Whether a value matches this pattern, adding the variables it binds to what they match.
+    fn matches(&self, value: &Value, binds: &mut Vec<(Ident, Value)>) -> bool {
+        match (self, value) {
+            (Self::Wildcard(_), _) => true,
+            (Self::Name(name, IsVariant(false)), value) => {
+                binds.push((name.clone(), value.clone()));
+                true
+            }
+            (Self::Name(name, IsVariant(true)), Value::Enum(variant, _)) => *name == variant.name,
+            (Self::Variant { name, fields }, Value::Enum(variant, values)) => {
+                *name == variant.name
+                    && fields
+                        .iter()
+                        .zip(values)
+                        .all(|(field, value)| field.matches(value, binds))
+            }
+            (Self::Nmbr(n), Value::Int(m)) => n.into_inner() == *m,
+            (Self::Strg(s), Value::Str(t)) => s.as_ref() == t,
+            (Self::Bool(b), Value::Bool(c)) => b.into_inner() == *c,
+            _ => false,
+        }
+    }
+}
+
+impl Eval for Class {
+    type Output = ();
+
+    fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
+        let module = ctx.module().map(str::to_string);
+        let methods = self.body.methods.iter().map(|method| {
+            let func = Value::Func {
+                captures: Cells::new(),
+                params: method.params().map(|param| param.ident.clone()).collect(),
+                rule: Body::Nest(method.rule.clone()),
+                module: module.clone(),
+            };
+            (method.name.clone(), func)
+        });
+        let class = ClassInfo {
+            name: self.name.to_string(),
+            fields: self.fields().map(|field| field.ident.clone()).collect(),
+            methods: methods.collect(),
+        };
+        ctx.set(self.name.clone(), Value::Class(Rc::new(class)));
+        Ok(())
     }
 }
 
@@ -145,11 +1355,12 @@ impl Eval for Defn {
     fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
         let name = self.name.clone();
         let params = self.params.iter().cloned().map(|i| i.ident).collect();
-        let rule = self.rule.clone();
+        let rule = Body::Nest(self.rule.clone());
         let func = Value::Func {
-            captures: ctx.clone(),
+            captures: ctx.captures(&self.captures),
             params,
             rule,
+            module: ctx.module().map(str::to_string),
         };
 
         ctx.set(name, func);
@@ -170,7 +1381,14 @@ impl Eval for Blck {
 
     fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
         for stmt in &mut self.stmts {
-            let v = stmt.eval(ctx)?;
+            ctx.step().map_err(|e| e.at(stmt.span()))?;
+            // take the hook out while it runs, so that anything it evaluates isn't hooked too
+            if let Some(mut hook) = ctx.hook.take() {
+                let watched = hook.before(stmt, ctx);
+                ctx.hook = Some(hook);
+                watched?;
+            }
+            let v = stmt.eval(ctx).map_err(|e| e.at(stmt.span()))?;
             if v.is_some() {
                 return Ok(v);
             }
@@ -186,6 +1404,13 @@ impl Eval for Stmt {
 
     fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
         match self {
+            Self::Decl {
+                typed_ident, expn, ..
+            } => {
+                let value = expn.eval(ctx)?;
+                ctx.set(typed_ident.ident.clone(), value);
+                Ok(None)
+            }
             Self::Assgn { ident, expn, .. } => {
                 let value = expn.eval(ctx)?;
                 ctx.set(ident.clone(), value);
@@ -197,17 +1422,53 @@ impl Eval for Stmt {
                 let rhs = expn.eval(ctx)?;
                 let old = ctx.get_or(ident)?;
                 let new = match op {
-                    Updt::Plus(_) => old.expect_int()? + rhs.expect_int()?,
-                    Updt::Minus(_) => old.expect_int()? - rhs.expect_int()?,
+                    Updt::Plus(_) => old.expect_int()?.checked_add(rhs.expect_int()?),
+                    Updt::Minus(_) => old.expect_int()?.checked_sub(rhs.expect_int()?),
                 };
+                let new = new.ok_or(Error::overflow())?;
                 ctx.set(ident.clone(), new);
                 Ok(None)
             }
-            Self::Pass(_, _) => Ok(None),
+            Self::SetField { target, expn, .. } => {
+                let Some((Postfix::Field(_, name), postfixes)) = target.postfixes.split_last_mut()
+                else {
+                    unreachable!("the checker only allows assigning to fields");
+                };
+                let object = target.leaf.eval(ctx)?;
+                let object = eval_postfixes(object, postfixes, ctx)?;
+                let value = expn.eval(ctx)?;
+                object.expect_object()?.borrow_mut().set(name, value)?;
+                Ok(None)
+            }
+            Self::Pass(_, _) | Self::Doc(_, _) => Ok(None),
+            Self::Defn(defn) => {
+                defn.eval(ctx)?;
+                Ok(None)
+            }
             Self::Print(_, args, _) => {
-                for expn in args.iter_mut() {
-                    println!("{}", expn.eval(ctx)?);
+                let mut values = Vec::new();
+                let mut sep = " ".to_string();
+                let mut end = "\n".to_string();
+                for arg in args.iter_mut() {
+                    match arg {
+                        PrintArg::Pos(expn) => {
+                            let value = expn.eval(ctx)?;
+                            values.push(value.to_str(ctx)?);
+                        }
+                        PrintArg::Kw { name, value, .. } => {
+                            let value = value.eval(ctx)?.expect_str()?.to_string();
+                            if name == "sep" {
+                                sep = value;
+                            } else {
+                                end = value;
+                            }
+                        }
+                    }
                 }
+                let line = values.join(&sep) + &end;
+                ctx.io
+                    .write(&line)
+                    .map_err(|_| Error::raise(Exn::OSError, "could not write output"))?;
                 Ok(None)
             }
             Self::If {
@@ -222,8 +1483,39 @@ impl Eval for Stmt {
                     else_nest.eval(ctx)
                 }
             }
+            Self::Match { expn, cases, .. } => {
+                let value = expn.eval(ctx)?;
+                for case in cases.iter_mut() {
+                    let mut binds = Vec::new();
+                    if case.pattern.matches(&value, &mut binds) {
+                        for (name, value) in binds {
+                            ctx.set(name, value);
+                        }
+                        return case.nest.eval(ctx);
+                    }
+                }
+                // the checker makes sure some case matches
+                Err(Error::raise(Exn::ValueError, "no case matched"))
+            }
             Self::While { cond, nest, .. } => {
-                while cond.eval(ctx)?.expect_bool()? {
+                loop {
+                    // each check of the condition is a step, so that even an empty loop runs out
+                    ctx.step()?;
+                    if !cond.eval(ctx)?.expect_bool()? {
+                        return Ok(None);
+                    }
+                    let v = nest.eval(ctx)?;
+                    if v.is_some() {
+                        return Ok(v);
+                    }
+                }
+            }
+            Self::For {
+                var, iter, nest, ..
+            } => {
+                for item in iter.eval(ctx)?.expect_items()? {
+                    ctx.step()?;
+                    ctx.set(var.clone(), item);
                     let v = nest.eval(ctx)?;
                     if v.is_some() {
                         return Ok(v);
@@ -231,23 +1523,83 @@ impl Eval for Stmt {
                 }
                 Ok(None)
             }
+            Self::Assert { cond, msg, .. } => {
+                if !ctx.asserts || cond.eval(ctx)?.expect_bool()? {
+                    return Ok(None);
+                }
+                let msg = match msg.as_mut() {
+                    Some(msg) => msg.eval(ctx)?.expect_str()?.to_string().into(),
+                    None => "assertion failed".into(),
+                };
+                Err(Error {
+                    msg,
+                    ..Error::raise(Exn::AssertionError, "")
+                })
+            }
+            Self::Raise { class, msg, .. } => {
+                let msg = msg.eval(ctx)?.expect_str()?.to_string();
+                let exn = Exn::from_name(&class.to_string()).expect("checked exception class");
+                Err(Error {
+                    msg: msg.into(),
+                    ..Error::raise(exn, "")
+                })
+            }
+            Self::Try {
+                nest,
+                handlers,
+                finally,
+                ..
+            } => {
+                let mut result = nest.eval(ctx);
+                if let Err(e) = &result {
+                    let (kind, msg) = (e.kind, e.msg.to_string());
+                    if let Some(handler) = handlers.iter_mut().find(|h| h.catches(kind)) {
+                        if let Some(name) = handler.name() {
+                            ctx.set(name.clone(), msg);
+                        }
+                        result = handler.nest.eval(ctx);
+                    }
+                }
+                // an exception or return from the finally nest replaces what came before it
+                if let Some(finally) = finally.as_mut() {
+                    let v = finally.eval(ctx)?;
+                    if v.is_some() {
+                        return Ok(v);
+                    }
+                }
+                result
+            }
+            Self::Nonlocal { .. } => Ok(None),
+            Self::Global { names, .. } => {
+                if let Some(frame) = ctx.frames.last_mut() {
+                    frame.globals.extend(names.iter().cloned());
+                }
+                Ok(None)
+            }
             Self::ReturnExpn { expn, .. } => Ok(Some(expn.eval(ctx)?)),
             Self::Return { .. } => Ok(Some(Value::Unit)),
-            Self::FuncCall { name, args, .. } => {
-                let func = ctx.get_or(name)?.clone();
-                let args: Vec<_> = args
-                    .iter_mut()
-                    .map(|e| e.eval(ctx))
-                    .collect::<Result<_, _>>()?;
-
-                func.try_call_with(args)?;
-
+            Self::Call(call, _) => {
+                // the value, if any, is discarded
+                let _ = call.eval(ctx)?;
                 Ok(None)
             }
         }
     }
 }
 
+impl Handler {
+    /// Whether this handler catches an error, which must be an exception rather than the
+    /// program being stopped.
+    fn catches(&self, kind: ErrorKind) -> bool {
+        let ErrorKind::Failed(exn) = kind else {
+            return false;
+        };
+        self.class.as_prefix().is_none_or(|class| {
+            Exn::from_name(&class.to_string()).is_some_and(|class| class.catches(exn))
+        })
+    }
+}
+
 impl Eval for Expn {
     type Output = Value;
 
@@ -315,38 +1667,87 @@ impl Eval for Leaf {
             Self::Expn(e) => e.eval(ctx)?,
             Self::Nmbr(n) => n.into_inner().into(),
             Self::Strg(s) => s.as_ref().to_string().into(),
-            Self::FuncCall { name, args } => {
-                let func = ctx.get_or(name)?.clone();
+            Self::FStrg(fstrg) => {
+                let mut s = String::new();
+                for part in &mut fstrg.parts {
+                    match part {
+                        FPart::Text(text) => s.push_str(text),
+                        FPart::Expn(expn, spec) => {
+                            let value = expn.eval(ctx)?;
+                            let text = value.to_str(ctx)?;
+                            s.push_str(&spec.layout(&value, text));
+                        }
+                    }
+                }
+                s.into()
+            }
+            Self::FuncCall { module, name, args } => {
+                let func = ctx.callee(module.as_prefix(), name)?;
                 let args: Vec<_> = args
                     .iter_mut()
                     .map(|e| e.eval(ctx))
                     .collect::<Result<_, _>>()?;
 
-                func.try_call_with(args)?.ok_or("expected a value")?
+                // falling off the end of an `Optional` function returns None
+                func.try_call_with(name, args, ctx)?.unwrap_or(Value::Unit)
             }
             Self::Bool(b) => b.into_inner().into(),
-            Self::Name(n) => ctx.get_or(n)?.clone(),
+            Self::Name(n) => ctx.get_or(n)?,
             Self::Unit(_) => ().into(),
+            Self::SelfRef(token) => ctx.get_or(&Ident::new("self", token.span))?,
+            Self::Lambda(lambda) => Value::Func {
+                captures: ctx.captures(&lambda.captures),
+                params: lambda.params().map(|param| param.ident.clone()).collect(),
+                rule: Body::Expn(lambda.body.clone()),
+                module: ctx.module().map(str::to_string),
+            },
+            Self::List(items) => Value::List(Rc::new(
+                items
+                    .iter_mut()
+                    .map(|e| e.eval(ctx))
+                    .collect::<Result<_, _>>()?,
+            )),
             Self::Inpt(_, expn) => {
-                let res = expn.eval(ctx)?;
-                print!("{res}");
-                std::io::stdout().flush().expect("can flush stdout");
-                let mut buffer = String::new();
-                if std::io::stdin().read_line(&mut buffer).is_ok() {
-                    buffer.trim_end().to_string().into()
-                } else {
-                    return Err("could not read stdin");
-                }
+                let prompt = expn.eval(ctx)?.to_string();
+                ctx.io
+                    .write(&prompt)
+                    .map_err(|_| Error::raise(Exn::OSError, "could not write output"))?;
+                // like reading an empty line, at the end of the input
+                let line = ctx
+                    .io
+                    .read_line()
+                    .map_err(|_| Error::raise(Exn::OSError, "could not read input"))?;
+                line.unwrap_or_default().trim_end().to_string().into()
             }
-            Self::Int(_, expn) => {
-                let res = expn.eval(ctx)?;
+            Self::Int(_, args) => {
+                let mut args = args.iter_mut();
+                let res = args
+                    .next()
+                    .ok_or(Error::raise(
+                        Exn::TypeError,
+                        "unexpected number of arguments",
+                    ))?
+                    .eval(ctx)?;
+                if let Some(base) = args.next() {
+                    let base = base.eval(ctx)?.expect_int()?;
+                    let base = u32::try_from(base)
+                        .ok()
+                        .filter(|base| (2..=36).contains(base))
+                        .ok_or(Error::raise(
+                            Exn::ValueError,
+                            "int base must be between 2 and 36",
+                        ))?;
+                    return i128::from_str_radix(res.expect_str()?.trim(), base)
+                        .map(Value::from)
+                        .map_err(|_| Error::raise(Exn::ValueError, "couldn't convert to int"));
+                }
                 match res {
                     Value::Int(n) => n,
                     Value::Str(s) => {
                         if let Ok(n) = s.parse() {
                             n
                         } else {
-                            return Err("couldn't convert to int");
+                            return Err(Error::raise(Exn::ValueError, "couldn't convert to int"));
                         }
                     }
                     Value::Bool(b) => {
@@ -356,11 +1757,110 @@ impl Eval for Leaf {
                             0
                         }
                     }
-                    _ => return Err("couldn't convert to int"),
+                    _ => return Err(Error::raise(Exn::ValueError, "couldn't convert to int")),
                 }
                 .into()
             }
-            Self::Str(_, expn) => expn.eval(ctx)?.to_string().into(),
+            Self::Str(_, expn) => expn.eval(ctx)?.to_str(ctx)?.into(),
         })
     }
 }
+
+impl Spec {
+    /// Format a value, shown as the text, padded out to the width.
+    fn layout(&self, value: &Value, text: String) -> String {
+        let pad = self.width.saturating_sub(text.chars().count());
+        if let (true, Value::Int(n)) = (self.zero, value) {
+            let sign = if *n < 0 { "-" } else { "" };
+            return format!("{sign}{}{}", "0".repeat(pad), n.unsigned_abs());
+        }
+
+        // like Python, numbers go on the right by default
+        let default = if matches!(value, Value::Int(_)) {
+            Align::Right
+        } else {
+            Align::Left
+        };
+        let (left, right) = match self.align.unwrap_or(default) {
+            Align::Left => (0, pad),
+            Align::Right => (pad, 0),
+            Align::Center => (pad / 2, pad - pad / 2),
+        };
+        let fill = |n| std::iter::repeat_n(self.fill, n).collect::<String>();
+        fill(left) + &text + &fill(right)
+    }
+}
+
+impl Eval for Indx {
+    type Output = Value;
+
+    fn eval(&mut self, ctx: &mut Context) -> Result<Self::Output, Error> {
+        let value = self.leaf.eval(ctx)?;
+        eval_postfixes(value, self.postfixes.iter_mut(), ctx)
+    }
+}
+
+/// The item of a list, or character of a str, at an index.
+fn item_at(value: &Value, index: i128) -> Result<Value, Error> {
+    match value {
+        Value::List(items) => Ok(items[position(index, items.len())?].clone()),
+        Value::Str(s) => {
+            let index = position(index, s.chars().count())?;
+            Ok(s.chars()
+                .nth(index)
+                .expect("index is in range")
+                .to_string()
+                .into())
+        }
+        _ => Err(Error::raise(
+            Exn::TypeError,
+            "type error: expected list or str",
+        )),
+    }
+}
+
+/// Where an index points in a list or str of the given length.
+fn position(index: i128, len: usize) -> Result<usize, Error> {
+    // like Python, negative indices count back from the end
+    let len = i128::try_from(len).expect("lists fit in an i128");
+    let index = if index < 0 { index + len } else { index };
+    if !(0..len).contains(&index) {
+        return Err(Error::raise(Exn::IndexError, "index out of range"));
+    }
+    Ok(usize::try_from(index).expect("index is in range"))
+}
+
+/// The value left once the postfixes are applied to a value in turn.
+fn eval_postfixes<'a>(
+    mut value: Value,
+    postfixes: impl IntoIterator<Item = &'a mut Postfix>,
+    ctx: &mut Context,
+) -> Result<Value, Error> {
+    // what a call goes by, which is the method's name for a method
+    let mut callee = None;
+    for postfix in postfixes {
+        let named = callee.take();
+        value = match postfix {
+            Postfix::Index(index) => {
+                let index = index.eval(ctx)?.expect_int()?;
+                item_at(&value, index)?
+            }
+            Postfix::Call(args) => {
+                // otherwise the function has no name here, so the call goes by its parentheses
+                let name = named.unwrap_or_else(|| Ident::new("lambda", args.span()));
+                let args: Vec<_> = args
+                    .iter_mut()
+                    .map(|e| e.eval(ctx))
+                    .collect::<Result<_, _>>()?;
+                value
+                    .try_call_with(&name, args, ctx)?
+                    .unwrap_or(Value::Unit)
+            }
+            Postfix::Field(_, name) => {
+                callee = Some(name.clone());
+                Object::get(value.expect_object()?, name)?
+            }
+        };
+    }
+    Ok(value)
+}
@@ -0,0 +1,724 @@
+use std::collections::VecDeque;
+
+use parsel::{
+    ast::{LeftAssoc, Maybe, RightAssoc},
+    LineColumn, Span, Spanned, ToTokens,
+};
+
+use crate::ast::*;
+use crate::comment::{self, Comment};
+
+const INDENT: &str = "    ";
+
+/// Reformat a parsed program in canonical layout, carrying over the comments from its source.
+pub fn format(prgm: &Prgm, source: &str) -> String {
+    let mut printer = Printer::new(source);
+    prgm.pretty(&mut printer);
+    printer.finish()
+}
+
+pub struct Printer {
+    lines: Vec<String>,
+    out: String,
+    indent: usize,
+    comments: VecDeque<Comment>,
+    /// The last source line that has been printed, if anything has been printed in this block.
+    last_line: Option<usize>,
+}
+
+impl Printer {
+    fn new(source: &str) -> Self {
+        Self {
+            lines: source.lines().map(str::to_string).collect(),
+            out: String::new(),
+            indent: 0,
+            comments: comment::comments(source).into(),
+            last_line: None,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.comments_before(usize::MAX);
+        self.out
+    }
+
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    /// Start a new line at the current indentation, keeping at most one blank line from the
+    /// source above it.
+    fn line(&mut self, source_line: usize) {
+        if matches!(self.last_line, Some(last) if source_line > last + 1) {
+            self.out.push('\n');
+        }
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// Print every comment that starts before the given source line on its own line.
+    fn comments_before(&mut self, line: usize) {
+        while let Some(comment) = self.comments.front() {
+            if comment.line >= line {
+                break;
+            }
+            let comment = self.comments.pop_front().expect("comment exists");
+            self.line(comment.line);
+            self.write(&comment.text);
+            self.write("\n");
+            self.last_line = Some(comment.end_line);
+        }
+    }
+
+    /// Print every comment before the given source position in the statement being printed,
+    /// where it is in the statement, carrying on with the rest of the statement on a line of
+    /// its own after it.
+    fn comments_within(&mut self, at: LineColumn) {
+        while let Some(comment) = self.comments.front() {
+            if (comment.line, comment.column) >= (at.line, at.column) {
+                break;
+            }
+            let comment = self.comments.pop_front().expect("comment exists");
+            let code = self.out.trim_end_matches(' ').len();
+            self.out.truncate(code);
+            if self.out.ends_with('\n') {
+                self.continuation();
+            } else if comment.trailing {
+                self.write(" ");
+            } else {
+                self.write("\n");
+                self.continuation();
+            }
+            self.write(&comment.text);
+            self.write("\n");
+            self.continuation();
+        }
+    }
+
+    /// Indent a line that carries on a statement begun above it.
+    fn continuation(&mut self) {
+        for _ in 0..=self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// End the line, along with a comment which directly follows the code ending at the given
+    /// source position.
+    fn end_line(&mut self, end: LineColumn) {
+        if let Some(comment) = self.comments.front() {
+            let follows = comment.trailing
+                && comment.line == end.line
+                && self.lines[end.line - 1]
+                    .chars()
+                    .take(comment.column)
+                    .skip(end.column)
+                    .all(char::is_whitespace);
+            if follows {
+                let comment = self.comments.pop_front().expect("comment exists");
+                self.write(" ");
+                self.write(&comment.text);
+            }
+        }
+        self.write("\n");
+        self.last_line = Some(end.line);
+    }
+
+    /// Print an item spanning some source lines on its own line, along with its comments.
+    fn item(&mut self, item: &(impl Pretty + Spanned)) {
+        let span = item.span();
+        self.comments_before(span.start().line);
+        self.line(span.start().line);
+        item.pretty(self);
+        self.end_line(span.end());
+    }
+}
+
+pub trait Pretty {
+    fn pretty(&self, p: &mut Printer);
+}
+
+impl Pretty for Prgm {
+    fn pretty(&self, p: &mut Printer) {
+        for import in &self.imports {
+            p.item(import);
+        }
+        if !self.imports.is_empty() {
+            // as are the imports, from what follows them
+            p.last_line = Some(0);
+        }
+        for enm in &self.enums {
+            p.item(enm);
+            // as are enums
+            p.last_line = Some(0);
+        }
+        for class in &self.classes {
+            p.item(class);
+            // as are classes
+            p.last_line = Some(0);
+        }
+        for defn in &self.defns {
+            p.item(defn);
+            // definitions are always set apart by a blank line
+            p.last_line = Some(0);
+        }
+        for stmt in &self.main.stmts {
+            p.item(stmt);
+        }
+    }
+}
+
+impl Pretty for Import {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::From { module, names, .. } => {
+                p.write("from ");
+                p.write(&module.to_string());
+                p.write(" import ");
+                let names: Vec<_> = names.iter().map(ToString::to_string).collect();
+                p.write(&names.join(", "));
+            }
+            Self::Module { module, .. } => {
+                p.write("import ");
+                p.write(&module.to_string());
+            }
+        }
+        p.write(";");
+    }
+}
+
+impl Pretty for Defn {
+    fn pretty(&self, p: &mut Printer) {
+        p.write("def ");
+        p.write(&self.name.to_string());
+        p.write("(");
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                p.write(", ");
+            }
+            param.pretty(p);
+        }
+        p.write(")");
+        if let Some(ret) = self.ret.as_prefix() {
+            p.write(" -> ");
+            ret.ty.pretty(p);
+        }
+        p.write(" ");
+        self.rule.pretty(p);
+    }
+}
+
+impl Pretty for Enum {
+    fn pretty(&self, p: &mut Printer) {
+        p.write("enum ");
+        p.write(&self.name.to_string());
+        p.write(" {");
+        let span = self.variants.span();
+        let open = span.start();
+        p.end_line(LineColumn {
+            line: open.line,
+            column: open.column + 1,
+        });
+        p.indent += 1;
+        p.last_line = None;
+        // one variant to a line, each with a comma after it
+        for variant in self.variants.iter() {
+            p.item(variant);
+        }
+        p.comments_before(span.end().line);
+        p.indent -= 1;
+        p.line(0);
+        p.write("}");
+    }
+}
+
+impl Pretty for Variant {
+    fn pretty(&self, p: &mut Printer) {
+        p.write(&self.name.to_string());
+        if let Some(fields) = self.fields.as_prefix() {
+            p.write("(");
+            for (i, ty) in fields.iter().enumerate() {
+                if i > 0 {
+                    p.write(", ");
+                }
+                ty.pretty(p);
+            }
+            p.write(")");
+        }
+        p.write(",");
+    }
+}
+
+impl Pretty for Class {
+    fn pretty(&self, p: &mut Printer) {
+        p.write("class ");
+        p.write(&self.name.to_string());
+        p.write(" {");
+        let span = self.body.span();
+        let open = span.start();
+        p.end_line(LineColumn {
+            line: open.line,
+            column: open.column + 1,
+        });
+        p.indent += 1;
+        p.last_line = None;
+        for field in self.body.fields.iter() {
+            p.item(field);
+        }
+        for method in self.body.methods.iter() {
+            // methods are set apart by a blank line, like definitions
+            if p.last_line.is_some() {
+                p.last_line = Some(0);
+            }
+            p.item(method);
+        }
+        p.comments_before(span.end().line);
+        p.indent -= 1;
+        p.line(0);
+        p.write("}");
+    }
+}
+
+impl Pretty for Method {
+    fn pretty(&self, p: &mut Printer) {
+        p.write("def ");
+        p.write(&self.name.to_string());
+        p.write("(self");
+        for param in self.params() {
+            p.write(", ");
+            param.pretty(p);
+        }
+        p.write(")");
+        if let Some(ret) = self.ret.as_prefix() {
+            p.write(" -> ");
+            ret.ty.pretty(p);
+        }
+        p.write(" ");
+        self.rule.pretty(p);
+    }
+}
+
+impl Pretty for Field {
+    fn pretty(&self, p: &mut Printer) {
+        self.typed_ident.pretty(p);
+        p.write(";");
+    }
+}
+
+impl Pretty for Nest {
+    fn pretty(&self, p: &mut Printer) {
+        let span = self.span();
+        let open = span.start();
+        p.write("{");
+        p.end_line(LineColumn {
+            line: open.line,
+            column: open.column + 1,
+        });
+        p.indent += 1;
+        p.last_line = None;
+        for stmt in &self.block.stmts {
+            p.item(stmt);
+        }
+        p.comments_before(span.end().line);
+        p.indent -= 1;
+        p.line(0);
+        p.write("}");
+    }
+}
+
+impl Pretty for Stmt {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::Decl {
+                typed_ident, expn, ..
+            } => {
+                typed_ident.pretty(p);
+                p.write(" = ");
+                expn.pretty(p);
+                p.write(";");
+            }
+            Self::Assgn { ident, expn, .. } => {
+                p.write(&ident.to_string());
+                p.write(" = ");
+                expn.pretty(p);
+                p.write(";");
+            }
+            Self::Updt {
+                ident, op, expn, ..
+            } => {
+                p.write(&ident.to_string());
+                p.write(" ");
+                p.write(&op.to_token_stream().to_string());
+                p.write(" ");
+                expn.pretty(p);
+                p.write(";");
+            }
+            Self::SetField { target, expn, .. } => {
+                target.pretty(p);
+                p.write(" = ");
+                expn.pretty(p);
+                p.write(";");
+            }
+            Self::Pass(_, _) => p.write("pass;"),
+            Self::Defn(defn) => defn.pretty(p),
+            Self::Doc(doc, _) => {
+                p.write(&doc.token().to_string());
+                p.write(";");
+            }
+            Self::Print(_, args, _) => {
+                p.write("print");
+                args.iter().pretty_args(p, args.span());
+                p.write(";");
+            }
+            Self::If {
+                cond,
+                if_nest,
+                else_nest,
+                ..
+            } => {
+                p.write("if ");
+                cond.pretty(p);
+                p.write(" ");
+                if_nest.pretty(p);
+                p.write(" else ");
+                else_nest.pretty(p);
+            }
+            Self::Match { expn, cases, .. } => {
+                p.write("match ");
+                expn.pretty(p);
+                p.write(" {");
+                let span = cases.span();
+                let open = span.start();
+                p.end_line(LineColumn {
+                    line: open.line,
+                    column: open.column + 1,
+                });
+                p.indent += 1;
+                p.last_line = None;
+                for case in cases.iter() {
+                    p.item(case);
+                }
+                p.comments_before(span.end().line);
+                p.indent -= 1;
+                p.line(0);
+                p.write("}");
+            }
+            Self::While { cond, nest, .. } => {
+                p.write("while ");
+                cond.pretty(p);
+                p.write(" ");
+                nest.pretty(p);
+            }
+            Self::For {
+                var, iter, nest, ..
+            } => {
+                p.write("for ");
+                p.write(&var.to_string());
+                p.write(" in ");
+                iter.pretty(p);
+                p.write(" ");
+                nest.pretty(p);
+            }
+            Self::Assert { cond, msg, .. } => {
+                p.write("assert ");
+                cond.pretty(p);
+                if let Some(msg) = msg.as_ref() {
+                    p.write(", ");
+                    msg.pretty(p);
+                }
+                p.write(";");
+            }
+            Self::Raise { class, msg, .. } => {
+                p.write("raise ");
+                p.write(&class.to_string());
+                std::iter::once(&**msg).pretty_args(p, msg.span());
+                p.write(";");
+            }
+            Self::Try {
+                nest,
+                handlers,
+                finally,
+                ..
+            } => {
+                p.write("try ");
+                nest.pretty(p);
+                for handler in handlers.iter() {
+                    p.write(" except ");
+                    if let Some(class) = handler.class.as_prefix() {
+                        p.write(&class.to_string());
+                        p.write(" ");
+                    }
+                    if let Some(name) = handler.name() {
+                        p.write("as ");
+                        p.write(&name.to_string());
+                        p.write(" ");
+                    }
+                    handler.nest.pretty(p);
+                }
+                if let Some(finally) = finally.as_ref() {
+                    p.write(" finally ");
+                    finally.pretty(p);
+                }
+            }
+            Self::Nonlocal { names, .. } | Self::Global { names, .. } => {
+                let keyword = if matches!(self, Self::Nonlocal { .. }) {
+                    "nonlocal "
+                } else {
+                    "global "
+                };
+                p.write(keyword);
+                let names: Vec<_> = names.iter().map(ToString::to_string).collect();
+                p.write(&names.join(", "));
+                p.write(";");
+            }
+            Self::ReturnExpn { expn, .. } => {
+                p.write("return ");
+                expn.pretty(p);
+                p.write(";");
+            }
+            Self::Return { .. } => p.write("return;"),
+            Self::Call(call, _) => {
+                call.pretty(p);
+                p.write(";");
+            }
+        }
+    }
+}
+
+/// Parenthesized, comma-separated arguments, from the given parentheses in the source.
+trait PrettyArgs {
+    fn pretty_args(self, p: &mut Printer, parens: Span);
+}
+
+impl<'a, T: Pretty + Spanned + 'a, I: Iterator<Item = &'a T>> PrettyArgs for I {
+    fn pretty_args(self, p: &mut Printer, parens: Span) {
+        p.write("(");
+        for (i, arg) in self.enumerate() {
+            if i > 0 {
+                p.write(", ");
+            }
+            p.comments_within(arg.span().start());
+            arg.pretty(p);
+        }
+        p.comments_within(parens.end());
+        p.write(")");
+    }
+}
+
+impl<T: Pretty> Pretty for Box<T> {
+    fn pretty(&self, p: &mut Printer) {
+        self.as_ref().pretty(p);
+    }
+}
+
+impl Pretty for Maybe<Qual> {
+    fn pretty(&self, p: &mut Printer) {
+        if let Some(qual) = self.as_prefix() {
+            p.write(&qual.module.to_string());
+            p.write(".");
+        }
+    }
+}
+
+impl Pretty for PrintArg {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::Kw { name, value, .. } => {
+                p.write(&name.to_string());
+                p.write("=");
+                value.pretty(p);
+            }
+            Self::Pos(expn) => expn.pretty(p),
+        }
+    }
+}
+
+impl Pretty for Expn {
+    fn pretty(&self, p: &mut Printer) {
+        self.0.pretty(p);
+    }
+}
+
+impl<B: ToTokens, C: Pretty + ToTokens> Pretty for LeftAssoc<B, C> {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::Binary { lhs, op, rhs } => {
+                lhs.pretty(p);
+                p.write(" ");
+                p.write(&op.to_token_stream().to_string());
+                p.write(" ");
+                p.comments_within(rhs.span().start());
+                rhs.pretty(p);
+            }
+            Self::Rhs(expn) => expn.pretty(p),
+        }
+    }
+}
+
+impl<B: ToTokens, C: Pretty + ToTokens> Pretty for RightAssoc<B, C> {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::Binary { lhs, op, rhs } => {
+                lhs.pretty(p);
+                p.write(" ");
+                p.write(&op.to_token_stream().to_string());
+                p.write(" ");
+                p.comments_within(rhs.span().start());
+                rhs.pretty(p);
+            }
+            Self::Lhs(expn) => expn.pretty(p),
+        }
+    }
+}
+
+impl<U: Unop + ToTokens, C: Pretty + ToTokens> Pretty for UnExp<U, C> {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::Op(op, child) => {
+                p.write(&op.to_token_stream().to_string());
+                p.write(" ");
+                child.pretty(p);
+            }
+            Self::Child(expn) => expn.pretty(p),
+        }
+    }
+}
+
+impl Pretty for Case {
+    fn pretty(&self, p: &mut Printer) {
+        p.write("case ");
+        self.pattern.pretty(p);
+        p.write(" ");
+        self.nest.pretty(p);
+    }
+}
+
+impl Pretty for Pattern {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::Wildcard(_) => p.write("_"),
+            Self::Nmbr(n) => p.write(&n.to_string()),
+            Self::Strg(s) => p.write(&s.token().to_string()),
+            Self::Bool(b) => p.write(&b.to_string()),
+            Self::Variant { name, fields } => {
+                p.write(&name.to_string());
+                p.write("(");
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        p.write(", ");
+                    }
+                    field.pretty(p);
+                }
+                p.write(")");
+            }
+            Self::Name(name, _) => p.write(&name.to_string()),
+        }
+    }
+}
+
+impl Pretty for Leaf {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::Inpt(_, expn) => {
+                p.write("input");
+                std::iter::once(&**expn).pretty_args(p, expn.span());
+            }
+            Self::Int(_, args) => {
+                p.write("int");
+                args.iter().pretty_args(p, args.span());
+            }
+            Self::Str(_, expn) => {
+                p.write("str");
+                std::iter::once(&**expn).pretty_args(p, expn.span());
+            }
+            Self::FuncCall { module, name, args } => {
+                module.pretty(p);
+                p.write(&name.to_string());
+                args.iter().pretty_args(p, args.span());
+            }
+            Self::Nmbr(n) => p.write(&n.to_string()),
+            Self::Strg(s) => p.write(&s.token().to_string()),
+            Self::FStrg(fstrg) => {
+                p.write("f");
+                p.write(&fstrg.lit.token().to_string());
+            }
+            Self::Bool(b) => p.write(&b.to_string()),
+            Self::Name(name) => p.write(&name.to_string()),
+            Self::Unit(_) => p.write("None"),
+            Self::SelfRef(_) => p.write("self"),
+            Self::Lambda(lambda) => {
+                p.write("lambda");
+                for (i, param) in lambda.params().enumerate() {
+                    p.write(if i > 0 { ", " } else { " " });
+                    param.pretty(p);
+                }
+                p.write(" -> ");
+                lambda.body.pretty(p);
+            }
+            Self::List(items) => {
+                p.write("[");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        p.write(", ");
+                    }
+                    p.comments_within(item.span().start());
+                    item.pretty(p);
+                }
+                p.comments_within(items.span().end());
+                p.write("]");
+            }
+            Self::Expn(expn) => std::iter::once(&**expn).pretty_args(p, expn.span()),
+        }
+    }
+}
+
+impl Pretty for Indx {
+    fn pretty(&self, p: &mut Printer) {
+        self.leaf.pretty(p);
+        for postfix in &self.postfixes {
+            match postfix {
+                Postfix::Index(index) => {
+                    p.write("[");
+                    index.pretty(p);
+                    p.write("]");
+                }
+                Postfix::Call(args) => args.iter().pretty_args(p, args.span()),
+                Postfix::Field(_, name) => {
+                    p.write(".");
+                    p.write(&name.to_string());
+                }
+            }
+        }
+    }
+}
+
+impl Pretty for TypedIdent {
+    fn pretty(&self, p: &mut Printer) {
+        p.write(&self.ident.to_string());
+        p.write(": ");
+        self.ty.pretty(p);
+    }
+}
+
+impl Pretty for Type {
+    fn pretty(&self, p: &mut Printer) {
+        match self {
+            Self::List(_, item) => {
+                p.write("list[");
+                item.pretty(p);
+                p.write("]");
+            }
+            Self::Optional(_, item) => {
+                p.write("Optional[");
+                item.pretty(p);
+                p.write("]");
+            }
+            Self::Func { params, ret, .. } => {
+                params.iter().pretty_args(p, params.span());
+                p.write(" -> ");
+                ret.pretty(p);
+            }
+            _ => p.write(&self.to_token_stream().to_string()),
+        }
+    }
+}
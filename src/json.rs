@@ -0,0 +1,337 @@
+use std::{fmt, iter::Peekable, str::Chars, str::FromStr};
+
+/// Just enough JSON to speak JSON-RPC.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Arr(Vec<Json>),
+    Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Build an object from its fields, in order.
+    pub fn obj<'a>(fields: impl IntoIterator<Item = (&'a str, Json)>) -> Self {
+        Self::Obj(
+            fields
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    }
+
+    /// Look up a field of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        if let Self::Obj(fields) = self {
+            fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+        } else {
+            None
+        }
+    }
+
+    /// Look up a field nested inside several objects.
+    pub fn at(&self, path: &[&str]) -> Option<&Json> {
+        path.iter().try_fold(self, |json, key| json.get(key))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let Self::Str(s) = self {
+            Some(s)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        if let &Self::Num(n) = self {
+            // JSON-RPC only ever uses small whole numbers
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            (n >= 0.0 && n.fract() == 0.0).then_some(n as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_arr(&self) -> Option<&[Json]> {
+        if let Self::Arr(items) = self {
+            Some(items)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        Self::Num(n as f64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Self::Str(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Self::Str(s)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Self {
+        Self::Arr(items)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(opt: Option<T>) -> Self {
+        opt.map_or(Self::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Num(n) => write!(f, "{n}"),
+            Self::Str(s) => write_str(f, s),
+            Self::Arr(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Obj(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl FromStr for Json {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars().peekable();
+        let json = parse(&mut chars)?;
+        skip_space(&mut chars);
+        if chars.next().is_some() {
+            return Err("trailing characters after JSON");
+        }
+        Ok(json)
+    }
+}
+
+fn skip_space(chars: &mut Peekable<Chars<'_>>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect_word(chars: &mut Peekable<Chars<'_>>, word: &str) -> Result<(), &'static str> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            return Err("invalid JSON literal");
+        }
+    }
+    Ok(())
+}
+
+fn parse(chars: &mut Peekable<Chars<'_>>) -> Result<Json, &'static str> {
+    skip_space(chars);
+    Ok(match chars.peek().ok_or("unexpected end of JSON")? {
+        'n' => {
+            expect_word(chars, "null")?;
+            Json::Null
+        }
+        't' => {
+            expect_word(chars, "true")?;
+            Json::Bool(true)
+        }
+        'f' => {
+            expect_word(chars, "false")?;
+            Json::Bool(false)
+        }
+        '"' => Json::Str(parse_str(chars)?),
+        '[' => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_space(chars);
+            if chars.next_if_eq(&']').is_none() {
+                loop {
+                    items.push(parse(chars)?);
+                    skip_space(chars);
+                    match chars.next() {
+                        Some(',') => (),
+                        Some(']') => break,
+                        _ => return Err("expected `,` or `]` in JSON array"),
+                    }
+                }
+            }
+            Json::Arr(items)
+        }
+        '{' => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_space(chars);
+            if chars.next_if_eq(&'}').is_none() {
+                loop {
+                    skip_space(chars);
+                    let key = parse_str(chars)?;
+                    skip_space(chars);
+                    if chars.next() != Some(':') {
+                        return Err("expected `:` in JSON object");
+                    }
+                    fields.push((key, parse(chars)?));
+                    skip_space(chars);
+                    match chars.next() {
+                        Some(',') => (),
+                        Some('}') => break,
+                        _ => return Err("expected `,` or `}` in JSON object"),
+                    }
+                }
+            }
+            Json::Obj(fields)
+        }
+        _ => {
+            let mut num = String::new();
+            while let Some(c) =
+                chars.next_if(|c| matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            {
+                num.push(c);
+            }
+            Json::Num(num.parse().map_err(|_| "invalid JSON number")?)
+        }
+    })
+}
+
+fn parse_str(chars: &mut Peekable<Chars<'_>>) -> Result<String, &'static str> {
+    if chars.next() != Some('"') {
+        return Err("expected a JSON string");
+    }
+    let mut s = String::new();
+    loop {
+        match chars.next().ok_or("unterminated JSON string")? {
+            '"' => return Ok(s),
+            '\\' => s.push(match chars.next().ok_or("unterminated JSON string")? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let mut code = parse_hex(chars)?;
+                    // a surrogate pair spells a character outside the basic plane
+                    if (0xd800..0xdc00).contains(&code) {
+                        expect_word(chars, "\\u")?;
+                        let low = parse_hex(chars)?
+                            .checked_sub(0xdc00)
+                            .ok_or("invalid JSON string escape")?;
+                        code = 0x10000 + ((code - 0xd800) << 10) + low;
+                    }
+                    char::from_u32(code).ok_or("invalid JSON string escape")?
+                }
+                c => c,
+            }),
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars<'_>>) -> Result<u32, &'static str> {
+    let hex: String = chars.take(4).collect();
+    u32::from_str_radix(&hex, 16).map_err(|_| "invalid JSON string escape")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Json;
+
+    fn parse(s: &str) -> Json {
+        s.parse().expect("valid JSON")
+    }
+
+    #[test]
+    fn round_trips() {
+        for text in [
+            "null",
+            "true",
+            "[]",
+            "{}",
+            "-1.5",
+            r#"{"id":1,"params":{"uri":"file:///a.dwislpy","items":[null,false,"x"]}}"#,
+        ] {
+            assert_eq!(parse(text).to_string(), text);
+        }
+        let json = Json::obj([("text", "a \"quoted\"\\ line\n\tend\u{1}".into())]);
+        assert_eq!(parse(&json.to_string()), json);
+    }
+
+    #[test]
+    fn skips_whitespace() {
+        let json = parse(" { \"a\" : [ 1 , 2 ] ,\r\n \"b\" : { } } ");
+        assert_eq!(json.to_string(), r#"{"a":[1,2],"b":{}}"#);
+    }
+
+    #[test]
+    fn escapes() {
+        let json = Json::from("\"\\\n\r\t\u{1f}é");
+        assert_eq!(json.to_string(), r#""\"\\\n\r\t\u001fé""#);
+        let text = r#""\" \\ \/ \b \f \n \r \t \u00e9 \ud83d\ude00""#;
+        assert_eq!(parse(text), Json::from("\" \\ / \u{8} \u{c} \n \r \t é 😀"));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        for text in [
+            "",
+            "nul",
+            "[1,",
+            "{\"a\" 1}",
+            "\"open",
+            "\"\\u12\"",
+            "\"\\ud83d\"",
+            "1 2",
+        ] {
+            assert!(text.parse::<Json>().is_err(), "{text:?} should not parse");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! The DWISPY programming language, as a library for programs that embed it.
+//!
+//! A host parses source with [`parse`], then type-checks it with [`check`] and runs it with
+//! [`run`] in a [`Context`]. The context decides where `print` and `input` go and how much the
+//! program may do, and can define functions written in Rust for the program to call.
+//! Afterwards it still holds the program's globals, which convert back into Rust values.
+//!
+//! ```
+//! use dwislpy::{check::{ArrowType, Ty}, Context, Streams};
+//!
+//! let mut output = Vec::new();
+//! let streams = Streams { input: &b""[..], output: &mut output };
+//! let mut ctx = Context::default().with_io(streams);
+//! let double = ArrowType { params: vec![Ty::Int], return_type: Some(Ty::Int), defaults: 0 };
+//! ctx.define("double", double, |args| Ok((2 * i128::try_from(args[0].clone())?).into()))
+//!     .unwrap();
+//!
+//! let mut prgm = dwislpy::parse("x: int = double(21);\nprint(str(x));").unwrap();
+//! dwislpy::check(&mut prgm, &mut ctx).unwrap();
+//! dwislpy::run(&mut prgm, &mut ctx).unwrap();
+//!
+//! let x: i128 = ctx.global("x").cloned().map(i128::try_from).unwrap().unwrap();
+//! assert_eq!(x, 42);
+//! drop(ctx);
+//! assert_eq!(output, b"42\n");
+//! ```
+
+use std::time::Duration;
+
+pub mod ast;
+pub mod check;
+pub mod comment;
+pub mod debug;
+pub mod eval;
+pub mod fmt;
+mod json;
+pub mod lsp;
+pub mod module;
+mod pattern;
+pub mod stdlib;
+
+pub use ast::Prgm;
+pub use eval::{Context, Error, ErrorKind, Io, Stdio, Streams, Value};
+
+use check::{Check, SymTab};
+use eval::Eval;
+
+/// Parse source text into a program.
+///
+/// # Errors
+/// If the source isn't a valid program.
+///
+pub fn parse(source: &str) -> parsel::Result<Prgm> {
+    comment::strip(source).parse()
+}
+
+/// Type-check a program before running it in the given context, which may define functions.
+///
+/// The modules it imports are loaded into the context along the way, from its search path.
+///
+/// # Errors
+/// If the program or a module it imports is not well-typed, or a module can't be loaded.
+///
+pub fn check(prgm: &mut Prgm, ctx: &mut Context) -> Result<(), Error> {
+    let mut defs = ctx.import_types(&prgm.imports)?;
+    prgm.check(&mut defs, &mut SymTab::default())
+}
+
+/// Run a checked program, leaving its globals in the context.
+///
+/// Each function call recurses on the native stack. A context allows [`eval::HOST_DEPTH`] calls
+/// by default, which fits in a main thread's stack; deeper limits need the program to run on a
+/// thread with [`eval::stack_size`] for the context's maximum depth.
+///
+/// # Errors
+/// If the program fails, or exceeds the context's limits.
+///
+pub fn run(prgm: &mut Prgm, ctx: &mut Context) -> Result<(), Error> {
+    prgm.eval(ctx)
+}
+
+/// Define a checked program's functions and run its imports, but not its main block, so that
+/// the host can call the functions with [`Context::call`].
+///
+/// # Errors
+/// If an imported module fails as it runs, or exceeds the context's limits.
+///
+pub fn load(prgm: &mut Prgm, ctx: &mut Context) -> Result<(), Error> {
+    prgm.load(ctx)
+}
+
+/// How much a program is allowed to do before it is stopped.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_depth: usize,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: eval::HOST_DEPTH,
+            max_steps: None,
+            timeout: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Impose these limits on a context, with the timeout starting now.
+    pub fn apply(self, ctx: Context) -> Context {
+        let ctx = ctx.with_max_depth(self.max_depth);
+        let ctx = match self.max_steps {
+            Some(max_steps) => ctx.with_max_steps(max_steps),
+            None => ctx,
+        };
+        match self.timeout {
+            Some(timeout) => ctx.with_timeout(timeout),
+            None => ctx,
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use parsel::{syn::Ident, LineColumn, Span};
+
+use crate::ast::{self, Prgm};
+use crate::check::{Ref, SymTab};
+use crate::comment;
+use crate::eval::Context;
+use crate::json::Json;
+
+// completion item kinds, from the spec
+const FUNCTION: usize = 3;
+const VARIABLE: usize = 6;
+const KEYWORD: usize = 14;
+
+/// Serve the language server protocol over the given streams until the client exits.
+///
+/// # Errors
+/// If reading or writing a message fails.
+///
+pub fn serve(mut input: impl BufRead, output: impl Write) -> io::Result<()> {
+    let mut server = Server {
+        out: output,
+        docs: HashMap::new(),
+    };
+    while let Some(msg) = read_message(&mut input)? {
+        let method = msg.get("method").and_then(Json::as_str).unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        server.handle(method, &msg)?;
+    }
+    Ok(())
+}
+
+/// Read one message, with its `Content-Length` header, or nothing at the end of the stream.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut body = vec![0; length.ok_or_else(|| invalid("missing Content-Length"))?];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|_| invalid("message is not UTF-8"))?;
+    body.parse().map(Some).map_err(invalid)
+}
+
+/// What we know about an open file.
+struct Document {
+    /// The last text that parsed, which the names the checker resolved in it point into.
+    text: String,
+    funcs: Vec<Ref>,
+    vars: Vec<Ref>,
+    /// The docstrings of its functions and methods, by the name each is defined with.
+    docs: Vec<(Ident, String)>,
+}
+
+struct Server<W> {
+    out: W,
+    docs: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<W> {
+    fn send(&mut self, msg: Json) -> io::Result<()> {
+        let body = msg.to_string();
+        write!(self.out, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.out.flush()
+    }
+
+    fn respond(&mut self, id: Json, result: Json) -> io::Result<()> {
+        self.send(Json::obj([
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("result", result),
+        ]))
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> io::Result<()> {
+        self.send(Json::obj([
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ]))
+    }
+
+    fn handle(&mut self, method: &str, msg: &Json) -> io::Result<()> {
+        let id = msg.get("id").cloned();
+        let params = msg.get("params").unwrap_or(&Json::Null);
+        let uri = params
+            .at(&["textDocument", "uri"])
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        let result = match method {
+            "initialize" => Json::obj([
+                (
+                    "capabilities",
+                    Json::obj([
+                        // the client sends the whole text on every change
+                        ("textDocumentSync", 1.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("completionProvider", Json::obj([])),
+                    ]),
+                ),
+                ("serverInfo", Json::obj([("name", "dwislpy".into())])),
+            ]),
+            "textDocument/didOpen" => {
+                let text = params.at(&["textDocument", "text"]).and_then(Json::as_str);
+                return self.update(uri, text.unwrap_or_default());
+            }
+            "textDocument/didChange" => {
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_arr)
+                    .and_then(<[Json]>::last)
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                return self.update(uri, text.unwrap_or_default());
+            }
+            "textDocument/didClose" => {
+                self.docs.remove(&uri);
+                return self.publish(uri, Vec::new());
+            }
+            "textDocument/hover" => self.hover(&uri, params),
+            "textDocument/definition" => self.definition(&uri, params),
+            "textDocument/completion" => self.completion(&uri, params),
+            "shutdown" => Json::Null,
+            _ => match id {
+                Some(id) => {
+                    return self.send(Json::obj([
+                        ("jsonrpc", "2.0".into()),
+                        ("id", id),
+                        (
+                            "error",
+                            Json::obj([
+                                ("code", Json::Num(-32601.0)),
+                                ("message", format!("unsupported method {method}").into()),
+                            ]),
+                        ),
+                    ]))
+                }
+                // notifications we don't care about
+                None => return Ok(()),
+            },
+        };
+
+        match id {
+            Some(id) => self.respond(id, result),
+            None => Ok(()),
+        }
+    }
+
+    /// Re-analyze a document after it changes, and tell the client what is wrong with it.
+    fn update(&mut self, uri: String, text: &str) -> io::Result<()> {
+        let mut doc = self.docs.remove(&uri).unwrap_or(Document {
+            text: String::new(),
+            funcs: Vec::new(),
+            vars: Vec::new(),
+            docs: Vec::new(),
+        });
+
+        let mut diagnostics = Vec::new();
+        match comment::strip(text).parse::<Prgm>() {
+            Ok(mut prgm) => {
+                // modules are loaded afresh each time, since they may have changed too
+                let mut ctx = Context::default().with_search_path(dir(&uri));
+                let mut syms = SymTab::default();
+                doc.funcs = Vec::new();
+                doc.docs = Vec::new();
+                let errors = match ctx.import_types(&prgm.imports) {
+                    Ok(mut defs) => {
+                        let errors = prgm.check_all(&mut defs, &mut syms);
+                        doc.funcs = defs.refs;
+                        doc.docs = defs.docs;
+                        errors
+                    }
+                    Err(e) => vec![e],
+                };
+                for e in errors {
+                    // a problem in another module shows up on the import that led to it
+                    let span = e.trace.last().map_or(e.span, |call| Some(call.site.span()));
+                    diagnostics.push(diagnostic(text, span, &e.msg));
+                }
+                doc.text = text.to_string();
+                doc.vars = syms.refs;
+            }
+            // keep what we knew from the last good parse, along with the text it came from
+            Err(e) => diagnostics.push(diagnostic(text, Some(e.span()), &e.to_string())),
+        }
+
+        self.docs.insert(uri.clone(), doc);
+        self.publish(uri, diagnostics)
+    }
+
+    fn publish(&mut self, uri: String, diagnostics: Vec<Json>) -> io::Result<()> {
+        self.notify(
+            "textDocument/publishDiagnostics",
+            Json::obj([("uri", uri.into()), ("diagnostics", diagnostics.into())]),
+        )
+    }
+
+    /// Find the resolved name under the cursor.
+    fn lookup(&self, uri: &str, params: &Json) -> Option<(&Document, &Ref)> {
+        let doc = self.docs.get(uri)?;
+        let at = cursor(&doc.text, params)?;
+        let found = doc
+            .funcs
+            .iter()
+            .chain(&doc.vars)
+            .find(|r| contains(r.name.span(), at))?;
+        Some((doc, found))
+    }
+
+    fn hover(&self, uri: &str, params: &Json) -> Json {
+        let Some((doc, found)) = self.lookup(uri, params) else {
+            return Json::Null;
+        };
+
+        let mut value = format!("```dwislpy\n{}\n```", found.desc);
+        let docstring = doc
+            .docs
+            .iter()
+            .find(|(name, _)| name.span().start() == found.decl.span().start());
+        if let Some((_, docstring)) = docstring {
+            value.push_str("\n\n");
+            value.push_str(docstring);
+        }
+
+        Json::obj([
+            (
+                "contents",
+                Json::obj([("kind", "markdown".into()), ("value", value.into())]),
+            ),
+            ("range", range(&doc.text, found.name.span())),
+        ])
+    }
+
+    fn definition(&self, uri: &str, params: &Json) -> Json {
+        let Some((doc, found)) = self.lookup(uri, params) else {
+            return Json::Null;
+        };
+        // builtins aren't declared anywhere in the source
+        let span = found.decl.span();
+        if span.start() == span.end() {
+            return Json::Null;
+        }
+        Json::obj([
+            ("uri", uri.into()),
+            ("range", range(&doc.text, found.decl.span())),
+        ])
+    }
+
+    fn completion(&self, uri: &str, params: &Json) -> Json {
+        let mut items = Vec::new();
+        let mut seen = Vec::new();
+        if let Some(doc) = self.docs.get(uri) {
+            // only the names of the functions the cursor is in, and the globals
+            let at = cursor(&doc.text, params);
+            let visible = |r: &&Ref| {
+                r.scope
+                    .is_none_or(|scope| at.is_some_and(|at| contains(scope, at)))
+            };
+            let funcs = doc.funcs.iter().filter(visible).map(|r| (r, FUNCTION));
+            let vars = doc.vars.iter().filter(visible).map(|r| (r, VARIABLE));
+            for (found, kind) in funcs.chain(vars) {
+                let label = found.decl.to_string();
+                if !seen.contains(&label) {
+                    items.push(Json::obj([
+                        ("label", label.as_str().into()),
+                        ("kind", kind.into()),
+                        ("detail", found.desc.as_str().into()),
+                    ]));
+                    seen.push(label);
+                }
+            }
+        }
+        for keyword in ast::keywords() {
+            items.push(Json::obj([
+                ("label", keyword.into()),
+                ("kind", KEYWORD.into()),
+            ]));
+        }
+        items.into()
+    }
+}
+
+/// The directory of a `file://` URI, where the modules it imports are.
+fn dir(uri: &str) -> Option<PathBuf> {
+    let path = decode(uri.strip_prefix("file://")?);
+    Path::new(&path).parent().map(Path::to_path_buf)
+}
+
+/// Undo the percent-encoding of a URI, which spells bytes like spaces as `%20`.
+fn decode(s: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+    while let Some((&b, after)) = rest.split_first() {
+        let hex = after
+            .get(..2)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        let hex = hex.and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if b == b'%' => {
+                bytes.push(byte);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn diagnostic(text: &str, span: Option<Span>, msg: &str) -> Json {
+    let range = span.map_or_else(
+        || {
+            let start = position(text, LineColumn { line: 1, column: 0 });
+            Json::obj([("start", start.clone()), ("end", start)])
+        },
+        |span| range(text, span),
+    );
+    Json::obj([
+        ("range", range),
+        // an error
+        ("severity", 1.into()),
+        ("source", "dwislpy".into()),
+        ("message", msg.into()),
+    ])
+}
+
+fn range(text: &str, span: Span) -> Json {
+    Json::obj([
+        ("start", position(text, span.start())),
+        ("end", position(text, span.end())),
+    ])
+}
+
+/// Where the cursor of a request is in the text.
+fn cursor(text: &str, params: &Json) -> Option<LineColumn> {
+    let position = params.get("position")?;
+    let line = position.get("line")?.as_usize()?;
+    let character = position.get("character")?.as_usize()?;
+    Some(line_column(text, line, character))
+}
+
+/// Whether a position is within a span, or at its end.
+fn contains(span: Span, at: LineColumn) -> bool {
+    let (start, end) = (span.start(), span.end());
+    (start.line, start.column) <= (at.line, at.column)
+        && (at.line, at.column) <= (end.line, end.column)
+}
+
+/// Convert a line (from 1) and column (in characters) into an LSP position, which counts lines
+/// from 0 and columns in UTF-16 code units.
+fn position(text: &str, at: LineColumn) -> Json {
+    let line = text
+        .lines()
+        .nth(at.line.saturating_sub(1))
+        .unwrap_or_default();
+    let character: usize = line.chars().take(at.column).map(char::len_utf16).sum();
+    Json::obj([
+        ("line", at.line.saturating_sub(1).into()),
+        ("character", character.into()),
+    ])
+}
+
+/// The inverse of [`position`].
+fn line_column(text: &str, line: usize, character: usize) -> LineColumn {
+    let mut units = 0;
+    let column = text
+        .lines()
+        .nth(line)
+        .unwrap_or_default()
+        .chars()
+        .take_while(|c| {
+            units += c.len_utf16();
+            units <= character
+        })
+        .count();
+    LineColumn {
+        line: line + 1,
+        column,
+    }
+}
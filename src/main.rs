@@ -1,40 +1,304 @@
-use std::fs;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
 
-// use ast::Prgm;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use parsel::Span;
 
-mod ast;
-mod check;
-mod eval;
-
-use ast::Prgm;
-use eval::{Context, Eval};
+use dwislpy::{debug::Debugger, eval, fmt, lsp, Context, Error, ErrorKind, Limits, Prgm, Stdio};
 
 /// The slpy programming language.
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// The file to run
-    #[clap(value_parser)]
-    file: String,
+    #[clap(value_parser, required = true)]
+    file: Option<String>,
+
+    /// How many function calls can be in progress at once, up to 10000
+    #[clap(long, global = true, default_value_t = eval::MAX_DEPTH, value_parser = parse_depth)]
+    max_depth: usize,
+
+    /// Stop after running this many statements and loop iterations
+    #[clap(long, global = true)]
+    max_steps: Option<u64>,
+
+    /// Stop after running for this many seconds
+    #[clap(long, global = true, value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+
+    /// Another directory to look for imported modules in, after the file's own
+    #[clap(long = "path", global = true, value_name = "DIR")]
+    paths: Vec<PathBuf>,
+
+    /// Skip assert statements
+    #[clap(long, global = true)]
+    no_assert: bool,
+}
+
+/// How to set up the context that a file runs in.
+pub struct Options {
+    limits: Limits,
+    /// More directories to look for imported modules in, after the file's own.
+    paths: Vec<PathBuf>,
+    asserts: bool,
+}
+
+impl Options {
+    fn apply<'a>(&self, source: &str, ctx: Context<'a>) -> Context<'a> {
+        let dir = Path::new(source).parent().map(Path::to_path_buf);
+        let ctx = ctx
+            .with_search_path(dir.into_iter().chain(self.paths.iter().cloned()))
+            .with_asserts(self.asserts);
+        self.limits.apply(ctx)
+    }
+}
+
+/// The deepest `--max-depth`, which already takes a stack of more than a GiB.
+const DEPTH_CAP: usize = 10_000;
+
+fn parse_depth(arg: &str) -> Result<usize, String> {
+    let depth: usize = arg
+        .parse()
+        .map_err(|_| format!("`{arg}` is not a number"))?;
+    if depth > DEPTH_CAP {
+        return Err(format!("can be at most {DEPTH_CAP}"));
+    }
+    Ok(depth)
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let secs: f64 = arg
+        .parse()
+        .map_err(|_| format!("`{arg}` is not a number"))?;
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("`{arg}` is not a duration"))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Reformat a source file in place
+    Fmt {
+        /// The file to format
+        #[clap(value_parser)]
+        file: String,
+
+        /// Only check that the file is formatted, without changing it
+        #[clap(long)]
+        check: bool,
+    },
+    /// Run a language server for editors, over stdin and stdout
+    Lsp,
+    /// Run a file under an interactive debugger
+    Debug {
+        /// The file to debug
+        #[clap(value_parser)]
+        file: String,
+    },
+    /// Run each function in a file whose name starts with `test_`
+    Test {
+        /// The file to test
+        #[clap(value_parser)]
+        file: String,
+    },
+}
+
+/// Parse the contents of a source file.
+///
+/// # Errors
+/// If parsing fails, after saying where.
+///
+fn parse(source: &str, contents: &str) -> Result<Prgm, Error> {
+    dwislpy::parse(contents).map_err(|e| {
+        locate(source, e.span(), &e);
+        Error::new("parsing failed").at(e.span())
+    })
+}
+
+/// Type-check a program, saying where a problem with it is.
+///
+/// # Errors
+/// If type-checking fails.
+///
+fn check(source: &str, contents: &str, prgm: &mut Prgm, ctx: &mut Context) -> Result<(), Error> {
+    dwislpy::check(prgm, ctx).inspect_err(|e| {
+        if !e.trace.is_empty() {
+            // show the imports that led to a problem in another module
+            eprint!("{}", e.traceback(source, contents, ctx.modules()));
+        } else if let Some(span) = e.span {
+            locate(source, span, &e.msg);
+        }
+    })
+}
+
+/// Say where in the source file a problem is, like a compiler does.
+fn locate(source: &str, span: Span, msg: &dyn std::fmt::Display) {
+    let at = span.start();
+    eprintln!("{source}:{}:{}: {msg}", at.line, at.column + 1);
 }
 
 /// Run the source file.
 ///
 /// # Errors
-/// If parsing or evaluation fails.
+/// If parsing, type-checking, or evaluation fails, or the program exceeds its limits.
+///
+pub fn run(source: String, opts: &Options) -> Result<(), Error> {
+    let contents = fs::read_to_string(&source).expect("Should have been able to read the file");
+    execute(&source, &contents, opts.apply(&source, Context::default()))
+}
+
+/// Run the source file under the debugger, taking commands from stdin.
+///
+/// # Errors
+/// If parsing, type-checking, or evaluation fails, the program exceeds its limits, or the
+/// debugger stops the program.
 ///
-pub fn run(source: String) -> Result<(), &'static str> {
-    let contents = fs::read_to_string(source).expect("Should have been able to read the file");
-    let mut prgm: Prgm = contents.parse().map_err(|e| {
-        dbg!(e);
-        "parsing failed"
-    })?;
-    prgm.eval(&mut Context::default())?;
-    Ok(())
+pub fn debug(source: String, opts: &Options) -> Result<(), Error> {
+    let contents = fs::read_to_string(&source).expect("Should have been able to read the file");
+    // the debugger and the program take turns reading stdin, so neither can hold it locked
+    let debugger = Debugger::new(&source, &contents, Stdio, std::io::stdout());
+    execute(
+        &source,
+        &contents,
+        opts.apply(&source, Context::with_hook(debugger)),
+    )
+}
+
+fn execute(source: &str, contents: &str, mut ctx: Context) -> Result<(), Error> {
+    let mut prgm = parse(source, contents)?;
+    check(source, contents, &mut prgm, &mut ctx)?;
+    dwislpy::run(&mut prgm, &mut ctx)
+        .inspect_err(|e| eprint!("{}", e.traceback(source, contents, ctx.modules())))
 }
 
-fn main() -> Result<(), &'static str> {
+/// Run every test in the source file: each function whose name starts with `test_` and takes no
+/// parameters. Each runs in its own context, with the file's functions defined but without its
+/// main block having run, and passes unless it raises an exception.
+///
+/// # Errors
+/// If parsing or type-checking fails, or any test fails.
+///
+pub fn test(source: String, opts: &Options) -> Result<(), Error> {
+    let contents = fs::read_to_string(&source).expect("Should have been able to read the file");
+    let mut prgm = parse(&source, &contents)?;
+    let mut ctx = opts.apply(&source, Context::default());
+    check(&source, &contents, &mut prgm, &mut ctx)?;
+
+    let tests = prgm
+        .defns
+        .iter()
+        .filter(|defn| defn.name.to_string().starts_with("test_") && defn.params.is_empty())
+        .map(|defn| defn.name.to_string());
+    let (mut passed, mut failed) = (0, 0);
+    for name in tests {
+        // a fresh context and program, so that tests can't affect each other
+        let mut ctx = opts.apply(&source, Context::default());
+        let mut prgm = prgm.clone();
+        dwislpy::check(&mut prgm, &mut ctx)?;
+        print!("{name} ... ");
+        let result = dwislpy::load(&mut prgm, &mut ctx).and_then(|()| ctx.call(&name, Vec::new()));
+        match result {
+            Ok(_) => {
+                println!("ok");
+                passed += 1;
+            }
+            Err(e) => {
+                println!("FAILED");
+                print!("{}", e.traceback(&source, &contents, ctx.modules()));
+                match e.kind {
+                    ErrorKind::Failed(exn) => println!("{exn}: {}", e.msg),
+                    _ => println!("{}", e.msg),
+                }
+                failed += 1;
+            }
+        }
+    }
+
+    println!("\n{passed} passed, {failed} failed");
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err("some tests failed".into())
+    }
+}
+
+/// Format the source file, or with `check`, make sure it is already formatted.
+///
+/// # Errors
+/// If parsing fails, or if checking finds an unformatted file.
+///
+pub fn format(source: String, check: bool) -> Result<(), Error> {
+    let contents = fs::read_to_string(&source).expect("Should have been able to read the file");
+    let formatted = fmt::format(&parse(&source, &contents)?, &contents);
+    if formatted == contents {
+        Ok(())
+    } else if check {
+        eprintln!("{source} is not formatted");
+        Err("formatting check failed".into())
+    } else {
+        fs::write(source, formatted).map_err(|_| "could not write the file".into())
+    }
+}
+
+fn main() -> ExitCode {
     let args = Args::parse();
-    run(args.file)
+    let opts = Options {
+        limits: Limits {
+            max_depth: args.max_depth,
+            max_steps: args.max_steps,
+            timeout: args.timeout,
+        },
+        paths: args.paths,
+        asserts: !args.no_assert,
+    };
+    match args.command {
+        Some(Command::Fmt { file, check }) => report(format(file, check)),
+        Some(Command::Lsp) => report(
+            lsp::serve(std::io::stdin().lock(), std::io::stdout().lock())
+                .map_err(|_| "language server i/o failed".into()),
+        ),
+        Some(Command::Debug { file }) => interpret(opts, |opts| debug(file, opts)),
+        Some(Command::Test { file }) => interpret(opts, |opts| test(file, opts)),
+        None => {
+            let file = args.file.expect("clap requires a file");
+            interpret(opts, |opts| run(file, opts))
+        }
+    }
+}
+
+/// Run a command that evaluates programs, on a thread with enough stack for as many calls as
+/// the options allow.
+fn interpret(
+    opts: Options,
+    command: impl FnOnce(&Options) -> Result<(), Error> + Send + 'static,
+) -> ExitCode {
+    let interpreter = thread::Builder::new()
+        .stack_size(eval::stack_size(opts.limits.max_depth))
+        .spawn(move || report(command(&opts)));
+    match interpreter {
+        Ok(interpreter) => interpreter
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+        Err(_) => report(Err(
+            "could not reserve a stack that deep for --max-depth".into()
+        )),
+    }
+}
+
+/// Show how a command went, as the exit code and any error message.
+fn report(result: Result<(), Error>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e.msg);
+            ExitCode::from(e.kind.exit_code())
+        }
+    }
 }
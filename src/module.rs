@@ -0,0 +1,216 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use parsel::syn::Ident;
+
+use crate::ast::{Enum, Import, Prgm};
+use crate::check::{ArrowType, Check, DefTypes, SymTab, Ty};
+use crate::eval::{Call, Error, Vars};
+
+/// Another file of definitions, which programs can import.
+pub struct Module {
+    /// Where its source was found.
+    pub path: PathBuf,
+    pub source: String,
+    /// The program, once it has checked.
+    pub(crate) prgm: Option<Prgm>,
+    /// The signatures of its functions and the constructors of its classes and variants, which
+    /// calls from importers are checked against.
+    exports: HashMap<Ident, ArrowType>,
+    /// The types of the variants of its enums that have no fields, which importers can import
+    /// by name as values of their own.
+    values: HashMap<Ident, Ty>,
+    /// The types it has checked against, with the classes and enums whose values its functions
+    /// can hand out.
+    types: DefTypes,
+    pub(crate) globals: Vars,
+    /// Whether its top-level code has run, which only happens the first time it is imported.
+    pub(crate) ran: bool,
+}
+
+/// Every module loaded so far, and where to look for more.
+#[derive(Default)]
+pub struct Modules {
+    /// The directories to look in after the importing module's own.
+    pub(crate) search_path: Vec<PathBuf>,
+    /// The modules, by the path each was found at, so that modules of the same name in
+    /// different directories are kept apart.
+    loaded: HashMap<String, Module>,
+    /// Which module each import loaded, by the module the import is in, or nothing for the
+    /// main program, and the name it imports.
+    resolved: HashMap<(Option<String>, String), String>,
+    /// The modules being checked, outermost first, with the names they were imported by, which
+    /// must not import each other.
+    loading: Vec<(String, Ident)>,
+}
+
+impl Modules {
+    /// A module that has been loaded, by the path it was found at.
+    pub fn get(&self, key: &str) -> Option<&Module> {
+        self.loaded.get(key)
+    }
+
+    pub(crate) fn get_mut(&mut self, key: &str) -> Option<&mut Module> {
+        self.loaded.get_mut(key)
+    }
+
+    /// The path of the module that an import of the given name loaded, from the given module
+    /// or from the main program.
+    pub(crate) fn resolve(&self, importer: Option<&str>, name: &Ident) -> Option<&str> {
+        let import = (importer.map(str::to_string), name.to_string());
+        self.resolved.get(&import).map(String::as_str)
+    }
+
+    /// The signatures that code with these imports can call, besides the builtins, loading and
+    /// checking each module the first time it is imported.
+    ///
+    /// Modules are looked for in the importing module's directory, if it has one, and then
+    /// along the search path.
+    ///
+    /// # Errors
+    /// If a module can't be found or doesn't check, or the modules import each other.
+    ///
+    pub fn import_types<'a>(
+        &mut self,
+        imports: impl IntoIterator<Item = &'a Import>,
+        dir: Option<&Path>,
+        builtins: &DefTypes,
+    ) -> Result<DefTypes, Error> {
+        let mut defs = builtins.clone();
+        let importer = self.loading.last().map(|(key, _)| key.clone());
+        for import in imports {
+            let module = import.module();
+            let key = self.load(module, dir, builtins)?;
+            let loaded = &self.loaded[&key];
+            defs.import_named(&loaded.types);
+            let exports = &loaded.exports;
+            match import {
+                Import::From { names, .. } => {
+                    for name in names.iter() {
+                        if let Some(arrow) = exports.get(name) {
+                            defs.set(name.clone(), arrow.clone());
+                        } else if let Some(ty) = loaded.values.get(name) {
+                            defs.import_value(name.clone(), ty.clone());
+                        } else {
+                            let msg = format!("cannot import `{name}` from `{module}`");
+                            return Err(Error::from(msg).at(name.span()));
+                        }
+                    }
+                }
+                Import::Module { .. } => defs.import(module.clone(), exports.clone())?,
+            }
+            let import = (importer.clone(), module.to_string());
+            self.resolved.insert(import, key);
+        }
+        Ok(defs)
+    }
+
+    /// Parse and check a module, unless that has already been done, returning the path it was
+    /// found at.
+    fn load(
+        &mut self,
+        name: &Ident,
+        dir: Option<&Path>,
+        builtins: &DefTypes,
+    ) -> Result<String, Error> {
+        let file = format!("{name}.dwislpy");
+        let path = dir
+            .into_iter()
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(&file))
+            .find(|path| path.is_file())
+            .ok_or_else(|| Error::from(format!("no module named `{name}`")).at(name.span()))?;
+        // the same file reached by different routes is the same module
+        let key = fs::canonicalize(&path)
+            .unwrap_or_else(|_| path.clone())
+            .display()
+            .to_string();
+        if self
+            .loaded
+            .get(&key)
+            .is_some_and(|module| module.prgm.is_some())
+        {
+            return Ok(key);
+        }
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == key) {
+            let names: Vec<_> = self.loading[start..]
+                .iter()
+                .map(|(_, name)| name.to_string())
+                .collect();
+            let cycle = names.join(" -> ");
+            return Err(Error::from(format!("import cycle: {cycle} -> {name}")).at(name.span()));
+        }
+
+        let source = fs::read_to_string(&path).map_err(|_| {
+            Error::from(format!("could not read {}", path.display())).at(name.span())
+        })?;
+
+        // keep the source even if it doesn't check, to show where the problem is
+        let module = Module {
+            path: path.clone(),
+            source: source.clone(),
+            prgm: None,
+            exports: HashMap::new(),
+            values: HashMap::new(),
+            types: DefTypes::default(),
+            globals: Vars::default(),
+            ran: false,
+        };
+        self.loaded.insert(key.clone(), module);
+
+        self.loading.push((key.clone(), name.clone()));
+        let checked = self.check(&path, &source, builtins);
+        self.loading.pop();
+        let (prgm, defs) = checked.map_err(|mut e| {
+            // the innermost module is where the problem is
+            if e.trace.is_empty() {
+                e.module = Some(key.clone());
+            }
+            e.trace.push(Call {
+                site: name.clone(),
+                module: self.loading.last().map(|(key, _)| key.clone()),
+                callee: "<module>".to_string(),
+            });
+            e
+        })?;
+
+        let classes = prgm
+            .classes
+            .iter()
+            .map(|class| (class.name.clone(), class.arrow_type()));
+        let variants = prgm.enums.iter().flat_map(Enum::constructors);
+        let defns = prgm
+            .defns
+            .iter()
+            .map(|defn| (defn.name.clone(), defn.arrow_type()));
+        let values = prgm.enums.iter().flat_map(|enm| {
+            let ty = Ty::Named(enm.name.to_string());
+            let variants = enm.variants.iter();
+            let fieldless = variants.filter(|variant| variant.fields.as_prefix().is_none());
+            fieldless.map(move |variant| (variant.name.clone(), ty.clone()))
+        });
+        let values = values.collect();
+        let module = self.loaded.get_mut(&key).expect("module was just loaded");
+        module.exports = classes.chain(variants).chain(defns).collect();
+        module.values = values;
+        module.types = defs;
+        module.prgm = Some(prgm);
+        Ok(key)
+    }
+
+    fn check(
+        &mut self,
+        path: &Path,
+        source: &str,
+        builtins: &DefTypes,
+    ) -> Result<(Prgm, DefTypes), Error> {
+        let mut prgm =
+            crate::parse(source).map_err(|e| Error::new("parsing failed").at(e.span()))?;
+        let mut defs = self.import_types(&prgm.imports, path.parent(), builtins)?;
+        prgm.check(&mut defs, &mut SymTab::default())?;
+        Ok((prgm, defs))
+    }
+}
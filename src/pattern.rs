@@ -0,0 +1,210 @@
+//! Checking the cases of a `match`: that each can match a value the ones before it don't, and
+//! that together they match every value.
+//!
+//! This is the usefulness algorithm from Maranget's "Warnings for pattern matching": a pattern
+//! is useful after some others if there is a value it matches that they don't, and finding one
+//! gives an example of a value that a `match` misses.
+
+use std::fmt;
+
+use parsel::{Span, Spanned};
+
+use crate::ast::{Case, IsVariant, Pattern};
+use crate::check::{DefTypes, Ty};
+use crate::eval::Error;
+
+/// A pattern, with only what matters for which values it matches.
+#[derive(Clone, Debug)]
+enum Pat {
+    /// Matches anything, like a wildcard or a name.
+    Any,
+    /// Matches values made with the constructor, whose fields match the patterns.
+    Ctor(Ctor, Vec<Pat>),
+}
+
+/// One way of making a value, which no other constructor makes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Ctor {
+    Int(i128),
+    Str(String),
+    Bool(bool),
+    Variant(String),
+}
+
+impl From<&Pattern> for Pat {
+    fn from(pattern: &Pattern) -> Self {
+        match pattern {
+            Pattern::Wildcard(_) | Pattern::Name(_, IsVariant(false)) => Self::Any,
+            Pattern::Nmbr(n) => Self::Ctor(Ctor::Int(n.into_inner()), Vec::new()),
+            Pattern::Strg(s) => Self::Ctor(Ctor::Str(s.as_ref().to_string()), Vec::new()),
+            Pattern::Bool(b) => Self::Ctor(Ctor::Bool(b.into_inner()), Vec::new()),
+            Pattern::Name(name, IsVariant(true)) => {
+                Self::Ctor(Ctor::Variant(name.to_string()), Vec::new())
+            }
+            Pattern::Variant { name, fields } => {
+                let fields = fields.iter().map(|field| Self::from(&**field)).collect();
+                Self::Ctor(Ctor::Variant(name.to_string()), fields)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (ctor, fields) = match self {
+            Self::Any => return write!(f, "_"),
+            Self::Ctor(ctor, fields) => (ctor, fields),
+        };
+        match ctor {
+            Ctor::Int(n) => write!(f, "{n}"),
+            Ctor::Str(s) => write!(f, "{s:?}"),
+            Ctor::Bool(b) => write!(f, "{b}"),
+            Ctor::Variant(name) if fields.is_empty() => write!(f, "{name}"),
+            Ctor::Variant(name) => {
+                write!(f, "{name}(")?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{field}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Check that each case of a `match` on a value of the given type can match something the cases
+/// before it don't, and that some case matches every value.
+///
+/// # Errors
+/// At the first case that can never match, or else at the value matched on, if some value
+/// matches no case.
+///
+pub(crate) fn check_cases(cases: &[Case], ty: &Ty, on: Span, defs: &DefTypes) -> Result<(), Error> {
+    let tys = [ty.clone()];
+    let mut rows: Vec<Vec<Pat>> = Vec::new();
+    for case in cases {
+        let row = vec![Pat::from(&case.pattern)];
+        if useful(&rows, &row, &tys, defs).is_none() {
+            return Err(Error::new("unreachable case").at(case.pattern.span()));
+        }
+        rows.push(row);
+    }
+    match useful(&rows, &[Pat::Any], &tys, defs) {
+        Some(missing) => {
+            let msg = format!("match is missing a case for `{}`", missing[0]);
+            Err(Error::from(msg).at(on))
+        }
+        None => Ok(()),
+    }
+}
+
+/// The constructors of a type and the types of their fields, if it has only so many of them.
+fn all_ctors(ty: &Ty, defs: &DefTypes) -> Option<Vec<(Ctor, Vec<Ty>)>> {
+    match ty {
+        Ty::Bool => Some(vec![
+            (Ctor::Bool(true), Vec::new()),
+            (Ctor::Bool(false), Vec::new()),
+        ]),
+        Ty::Named(name) => {
+            let variants = defs.variants(name)?.iter();
+            let ctors = variants
+                .map(|(variant, fields)| (Ctor::Variant(variant.to_string()), fields.clone()));
+            Some(ctors.collect())
+        }
+        _ => None,
+    }
+}
+
+/// The types of the fields of a constructor of a type.
+fn field_tys(ctor: &Ctor, arity: usize, ty: &Ty, defs: &DefTypes) -> Vec<Ty> {
+    all_ctors(ty, defs)
+        .into_iter()
+        .flatten()
+        .find(|(other, _)| other == ctor)
+        .map_or_else(|| vec![Ty::Any; arity], |(_, fields)| fields)
+}
+
+/// The rest of a row, if its first pattern matches values made with the constructor, with
+/// patterns for the constructor's fields in its place.
+fn specialize(row: &[Pat], ctor: &Ctor, arity: usize) -> Option<Vec<Pat>> {
+    let (first, rest) = row.split_first()?;
+    let mut fields = match first {
+        Pat::Any => vec![Pat::Any; arity],
+        Pat::Ctor(other, fields) if other == ctor => fields.clone(),
+        Pat::Ctor(..) => return None,
+    };
+    fields.extend_from_slice(rest);
+    Some(fields)
+}
+
+/// Values that match the patterns of `row` but none of the rows before it, as patterns for each
+/// of the values, if there are any.
+fn useful(rows: &[Vec<Pat>], row: &[Pat], tys: &[Ty], defs: &DefTypes) -> Option<Vec<Pat>> {
+    let Some((first, rest)) = row.split_first() else {
+        // nothing is left to tell the values apart, so only an empty matrix misses them
+        return rows.is_empty().then(Vec::new);
+    };
+    let (ty, rest_tys) = tys.split_first().expect("a type for each pattern");
+
+    // find a value for a constructor, then rebuild the constructor around it
+    let with_ctor = |ctor: &Ctor, fields: &[Pat], field_tys: Vec<Ty>| {
+        let arity = fields.len();
+        let rows: Vec<_> = rows
+            .iter()
+            .filter_map(|r| specialize(r, ctor, arity))
+            .collect();
+        let row: Vec<_> = fields.iter().chain(rest).cloned().collect();
+        let tys: Vec<_> = field_tys
+            .into_iter()
+            .chain(rest_tys.iter().cloned())
+            .collect();
+        let mut found = useful(&rows, &row, &tys, defs)?;
+        let rest = found.split_off(arity);
+        let mut value = vec![Pat::Ctor(ctor.clone(), found)];
+        value.extend(rest);
+        Some(value)
+    };
+
+    if let Pat::Ctor(ctor, fields) = first {
+        return with_ctor(ctor, fields, field_tys(ctor, fields.len(), ty, defs));
+    }
+
+    let used: Vec<&Ctor> = rows
+        .iter()
+        .filter_map(|row| match row.first() {
+            Some(Pat::Ctor(ctor, _)) => Some(ctor),
+            _ => None,
+        })
+        .collect();
+    let ctors = all_ctors(ty, defs);
+    if let Some(ctors) = &ctors {
+        if ctors.iter().all(|(ctor, _)| used.contains(&ctor)) {
+            // every constructor is covered by some row, so each has to be tried in turn
+            return ctors.iter().find_map(|(ctor, field_tys)| {
+                let fields = vec![Pat::Any; field_tys.len()];
+                with_ctor(ctor, &fields, field_tys.clone())
+            });
+        }
+    }
+
+    // otherwise a value made with a constructor no row names only matches the rows that match
+    // anything first
+    let rows: Vec<_> = rows
+        .iter()
+        .filter(|row| matches!(row.first(), Some(Pat::Any)))
+        .map(|row| row[1..].to_vec())
+        .collect();
+    let mut found = useful(&rows, rest, rest_tys, defs)?;
+    let missing = ctors
+        .into_iter()
+        .flatten()
+        .find(|(ctor, _)| !used.contains(&ctor))
+        .filter(|_| !used.is_empty())
+        .map_or(Pat::Any, |(ctor, fields)| {
+            Pat::Ctor(ctor, vec![Pat::Any; fields.len()])
+        });
+    found.insert(0, missing);
+    Some(found)
+}
@@ -0,0 +1,139 @@
+use crate::check::{ArrowType, Ty};
+use crate::eval::{Context, Error, Exn, Value};
+
+/// Define every builtin function in the context.
+///
+/// # Errors
+/// If a builtin's signature is invalid.
+///
+pub fn install(ctx: &mut Context) -> Result<(), Error> {
+    use Ty::{Int, ListOrStr, Str};
+    let list = |item| Ty::List(Box::new(item));
+    let arrow = |params: &[Ty], return_type, defaults| ArrowType {
+        return_type: Some(return_type),
+        params: params.to_vec(),
+        defaults,
+    };
+
+    ctx.define("len", arrow(&[ListOrStr], Int, 0), len)?;
+    ctx.define("abs", arrow(&[Int], Int, 0), |args| {
+        Ok(args[0]
+            .expect_int()?
+            .checked_abs()
+            .ok_or(Error::overflow())?
+            .into())
+    })?;
+    ctx.define("min", arrow(&[Int, Int], Int, 0), |args| {
+        Ok(args[0].expect_int()?.min(args[1].expect_int()?).into())
+    })?;
+    ctx.define("max", arrow(&[Int, Int], Int, 0), |args| {
+        Ok(args[0].expect_int()?.max(args[1].expect_int()?).into())
+    })?;
+    ctx.define("ord", arrow(&[Str], Int, 0), ord)?;
+    ctx.define("chr", arrow(&[Int], Str, 0), chr)?;
+    ctx.define("upper", arrow(&[Str], Str, 0), |args| {
+        Ok(args[0].expect_str()?.to_uppercase().into())
+    })?;
+    ctx.define("lower", arrow(&[Str], Str, 0), |args| {
+        Ok(args[0].expect_str()?.to_lowercase().into())
+    })?;
+    ctx.define("strip", arrow(&[Str], Str, 0), |args| {
+        Ok(args[0].expect_str()?.trim().into())
+    })?;
+    ctx.define("split", arrow(&[Str, Str], list(Str), 1), split)?;
+    ctx.define("join", arrow(&[Str, list(Str)], Str, 0), join)?;
+    ctx.define("find", arrow(&[Str, Str], Int, 0), find)?;
+    ctx.define("replace", arrow(&[Str, Str, Str], Str, 0), |args| {
+        let s = args[0].expect_str()?;
+        let (from, to) = (args[1].expect_str()?, args[2].expect_str()?);
+        Ok(s.replace(from, to).into())
+    })?;
+    ctx.define_with_context("range", arrow(&[Int, Int, Int], list(Int), 2), range)?;
+    Ok(())
+}
+
+/// `len(xs)`: how many items are in a list, or characters in a str.
+fn len(args: Vec<Value>) -> Result<Value, Error> {
+    let len = match &args[0] {
+        Value::List(items) => items.len(),
+        other => other.expect_str()?.chars().count(),
+    };
+    Ok(i128::try_from(len).expect("lengths fit in an i128").into())
+}
+
+/// `ord(c)`: the code point of a single character.
+fn ord(args: Vec<Value>) -> Result<Value, Error> {
+    let mut chars = args[0].expect_str()?.chars();
+    let msg = "ord expected a single character";
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(i128::from(u32::from(c)).into()),
+        _ => Err(Error::raise(Exn::ValueError, msg)),
+    }
+}
+
+/// `chr(n)`: the character with a code point.
+fn chr(args: Vec<Value>) -> Result<Value, Error> {
+    u32::try_from(args[0].expect_int()?)
+        .ok()
+        .and_then(char::from_u32)
+        .map(|c| c.to_string().into())
+        .ok_or(Error::raise(Exn::ValueError, "chr argument out of range"))
+}
+
+/// `split(s)` or `split(s, sep)`: the parts of a str between whitespace, or a separator.
+fn split(args: Vec<Value>) -> Result<Value, Error> {
+    let s = args[0].expect_str()?;
+    let parts: Vec<&str> = match args.get(1) {
+        None => s.split_whitespace().collect(),
+        Some(sep) => match sep.expect_str()? {
+            "" => return Err(Error::raise(Exn::ValueError, "empty separator")),
+            sep => s.split(sep).collect(),
+        },
+    };
+    let parts: Vec<Value> = parts.into_iter().map(Value::from).collect();
+    Ok(parts.into())
+}
+
+/// `join(sep, parts)`: the parts of a list, with a separator between each.
+fn join(args: Vec<Value>) -> Result<Value, Error> {
+    let sep = args[0].expect_str()?;
+    let parts = Vec::<Value>::try_from(args[1].clone())?;
+    let parts: Result<Vec<&str>, _> = parts.iter().map(Value::expect_str).collect();
+    Ok(parts?.join(sep).into())
+}
+
+/// `find(s, sub)`: the index of the first character where a substring starts, or -1.
+fn find(args: Vec<Value>) -> Result<Value, Error> {
+    let s = args[0].expect_str()?;
+    let index = s.find(args[1].expect_str()?).map_or(-1, |at| {
+        i128::try_from(s[..at].chars().count()).expect("lengths fit in an i128")
+    });
+    Ok(index.into())
+}
+
+/// `range(stop)`, `range(start, stop)` or `range(start, stop, step)`: counting, like Python.
+/// Each number counts as a step, so a long range can't outlast the program's limits.
+fn range(args: Vec<Value>, ctx: &mut Context) -> Result<Value, Error> {
+    let (start, stop) = match &args[..] {
+        [stop] => (0, stop.expect_int()?),
+        [start, stop, ..] => (start.expect_int()?, stop.expect_int()?),
+        [] => unreachable!("the checker requires at least one argument"),
+    };
+    let step = args.get(2).map_or(Ok(1), Value::expect_int)?;
+    if step == 0 {
+        return Err(Error::raise(Exn::ValueError, "range step cannot be zero"));
+    }
+
+    let mut items = Vec::new();
+    let mut n = start;
+    while (step > 0 && n < stop) || (step < 0 && n > stop) {
+        ctx.step()?;
+        items.push(Value::from(n));
+        // past the largest int is past any stop
+        let Some(next) = n.checked_add(step) else {
+            break;
+        };
+        n = next;
+    }
+    Ok(items.into())
+}
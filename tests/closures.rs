@@ -0,0 +1,116 @@
+//! Closures share the variables they capture through reference-counted cells, so a function
+//! must not end up holding on to itself and keeping its variables alive forever.
+
+use std::rc::Rc;
+
+use dwislpy::{
+    check::{ArrowType, Ty},
+    eval::Value,
+    Context,
+};
+
+/// Run a program that can call `items()` for a list from the host, and tell what it left in
+/// `x` and how many places other than the host's own `Rc` still hold the list.
+fn run(source: &str) -> (i128, usize) {
+    let items = Rc::new(vec![Value::Int(1), Value::Int(2)]);
+    let given = items.clone();
+    let mut ctx = Context::default();
+    let arrow = ArrowType {
+        params: Vec::new(),
+        return_type: Some(Ty::List(Box::new(Ty::Int))),
+        defaults: 0,
+    };
+    ctx.define("items", arrow, move |_| Ok(Value::List(given.clone())))
+        .unwrap();
+
+    let mut prgm = dwislpy::parse(source).unwrap();
+    dwislpy::check(&mut prgm, &mut ctx).unwrap();
+    dwislpy::run(&mut prgm, &mut ctx).unwrap();
+    let x = ctx.global("x").cloned().map(i128::try_from);
+    (x.unwrap().unwrap(), Rc::strong_count(&items) - 1)
+}
+
+#[test]
+fn recursive_def_is_freed() {
+    let source = "
+def outer() -> int {
+    xs: list[int] = items();
+    def count(n: int) -> int {
+        if n == 0 {
+            return len(xs);
+        } else {
+            pass;
+        }
+        return count(n - 1) + 1;
+    }
+    return count(3);
+}
+x: int = outer();
+";
+    // once `outer` returns, nothing holds its `xs`, not even `count`, which captured it; only
+    // the host's `items` does
+    assert_eq!(run(source), (5, 1));
+}
+
+#[test]
+fn mutually_recursive_defs_are_freed() {
+    let source = "
+def outer() -> int {
+    xs: list[int] = items();
+    def even(n: int) -> bool {
+        if n == 0 {
+            return 0 < len(xs);
+        } else {
+            return odd(n - 1);
+        }
+    }
+    def odd(n: int) -> bool {
+        if n == 0 {
+            return false;
+        } else {
+            return even(n - 1);
+        }
+    }
+    if even(4) {
+        return 1;
+    } else {
+        return 0;
+    }
+}
+x: int = outer();
+";
+    assert_eq!(run(source), (1, 1));
+}
+
+#[test]
+fn returned_defs_keep_what_they_capture() {
+    let source = "
+def outer() -> (int) -> bool {
+    xs: list[int] = items();
+    def even(n: int) -> bool {
+        if n == 0 {
+            return 0 < len(xs);
+        } else {
+            return odd(n - 1);
+        }
+    }
+    def odd(n: int) -> bool {
+        if n == 0 {
+            return false;
+        } else {
+            return even(n - 1);
+        }
+    }
+    return odd;
+}
+odd: (int) -> bool = outer();
+x: int = 0;
+if odd(3) {
+    x = 1;
+} else {
+    pass;
+}
+";
+    // `odd` still reaches `even`, and through it `xs`
+    assert_eq!(run(source), (1, 2));
+}
@@ -0,0 +1,165 @@
+//! A scripted session with the language server: the messages an editor sends when it opens a
+//! file and hovers over a name, and what the server has to answer.
+
+use std::fs;
+
+/// Frame each message the way a client sends it.
+fn script(messages: &[String]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for body in messages {
+        input.extend(format!("Content-Length: {}\r\n\r\n{body}", body.len()).into_bytes());
+    }
+    input
+}
+
+/// Split what the server wrote back into its messages.
+fn replies(output: &[u8]) -> Vec<String> {
+    let mut output = std::str::from_utf8(output).expect("the server writes UTF-8");
+    let mut replies = Vec::new();
+    while let Some(rest) = output.strip_prefix("Content-Length: ") {
+        let (length, rest) = rest
+            .split_once("\r\n\r\n")
+            .expect("a header ends with a blank line");
+        let length: usize = length.parse().expect("the length is a number");
+        replies.push(rest[..length].to_string());
+        output = &rest[length..];
+    }
+    assert!(output.is_empty(), "trailing output {output:?}");
+    replies
+}
+
+#[test]
+fn open_and_hover() {
+    // the modules a file imports are beside it, even where its URI has to escape the path
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("lsp session");
+    fs::create_dir_all(&dir).unwrap();
+    let module = "def area(w: int, h: int) -> int {\n    return w * h;\n}\n";
+    fs::write(dir.join("geometry.dwislpy"), module).unwrap();
+    let uri = format!("file://{}/main.dwislpy", dir.display()).replace(' ', "%20");
+
+    let text = [
+        "import geometry;",
+        "def double(x: int) -> int {",
+        "    \\\"Twice x.\\\";",
+        "    return 2 * x;",
+        "}",
+        "def bad() -> int {",
+        "    return \\\"one\\\";",
+        "}",
+        "y: int = double(geometry.area(2, 3));",
+        "z: int = \\\"two\\\";",
+    ]
+    .join("\\n");
+    let input = script(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{}}}"#.into(),
+        r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#.into(),
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{uri}","languageId":"dwislpy","version":1,"text":"{text}"}}}}}}"#
+        ),
+        format!(
+            r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{{"textDocument":{{"uri":"{uri}"}},"position":{{"line":8,"character":10}}}}}}"#
+        ),
+        r#"{"jsonrpc":"2.0","id":3,"method":"shutdown"}"#.into(),
+        r#"{"jsonrpc":"2.0","method":"exit"}"#.into(),
+    ]);
+
+    let mut output = Vec::new();
+    dwislpy::lsp::serve(&input[..], &mut output).unwrap();
+    let replies = replies(&output);
+    assert_eq!(replies.len(), 4, "{replies:#?}");
+
+    assert!(replies[0].starts_with(r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities":"#));
+    assert!(replies[0].contains(r#""hoverProvider":true"#));
+
+    // every problem is reported, and the import was found
+    let range = |line, start, end| {
+        format!(
+            r#""range":{{"start":{{"line":{line},"character":{start}}},"end":{{"line":{line},"character":{end}}}}}"#
+        )
+    };
+    let diagnostic = |range: String, message: &str| {
+        format!(r#"{{{range},"severity":1,"source":"dwislpy","message":"{message}"}}"#)
+    };
+    let expected = format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{{"uri":"{uri}","diagnostics":[{},{}]}}}}"#,
+        diagnostic(range(5, 4, 7), "mismatched return type"),
+        diagnostic(range(9, 0, 15), "mismatched types"),
+    );
+    assert_eq!(replies[1], expected);
+
+    let expected = format!(
+        r#"{{"jsonrpc":"2.0","id":2,"result":{{"contents":{{"kind":"markdown","value":"```dwislpy\ndouble: (int) -> int\n```\n\nTwice x."}},{}}}}}"#,
+        range(8, 9, 15)
+    );
+    assert_eq!(replies[2], expected);
+
+    assert_eq!(replies[3], r#"{"jsonrpc":"2.0","id":3,"result":null}"#);
+}
+
+#[test]
+fn nested_docs_and_scoped_completion() {
+    let uri = "file:///tmp/nested.dwislpy";
+    let text = [
+        "class Point {",
+        "    x: int;",
+        "    def norm(self) -> int {",
+        "        \\\"Squared length.\\\";",
+        "        return self.x * self.x;",
+        "    }",
+        "}",
+        "def outer(n: int) -> int {",
+        "    def inner() -> int {",
+        "        \\\"One more than n.\\\";",
+        "        return n + 1;",
+        "    }",
+        "    local: int = inner();",
+        "    return local;",
+        "}",
+        "p: Point = Point(3);",
+        "y: int = p.norm() + outer(1);",
+    ]
+    .join("\\n");
+    let request = |id, method, line, character| {
+        format!(
+            r#"{{"jsonrpc":"2.0","id":{id},"method":"textDocument/{method}","params":{{"textDocument":{{"uri":"{uri}"}},"position":{{"line":{line},"character":{character}}}}}}}"#
+        )
+    };
+    let input = script(&[
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"capabilities":{}}}"#.into(),
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{uri}","languageId":"dwislpy","version":1,"text":"{text}"}}}}}}"#
+        ),
+        request(2, "hover", 12, 18),
+        request(3, "hover", 16, 12),
+        request(4, "completion", 13, 4),
+        request(5, "completion", 16, 0),
+        // an edit that doesn't parse leaves the last good analysis in place
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{uri}","version":2}},"contentChanges":[{{"text":"y: int = "}}]}}}}"#
+        ),
+        request(6, "hover", 16, 12),
+        r#"{"jsonrpc":"2.0","method":"exit"}"#.into(),
+    ]);
+
+    let mut output = Vec::new();
+    dwislpy::lsp::serve(&input[..], &mut output).unwrap();
+    let replies = replies(&output);
+    assert_eq!(replies.len(), 8, "{replies:#?}");
+
+    // the docstrings of nested functions and methods show too
+    assert!(replies[2].contains(r#"inner: () -> int\n```\n\nOne more than n."#));
+    assert!(replies[3].contains(r#"Point.norm: () -> int\n```\n\nSquared length."#));
+
+    // a function's locals are only offered inside it
+    let label = |name| format!(r#""label":"{name}""#);
+    assert!(replies[4].contains(&label("local")));
+    assert!(replies[4].contains(&label("n")));
+    assert!(replies[4].contains(&label("p")));
+    assert!(!replies[5].contains(&label("local")));
+    assert!(!replies[5].contains(&label("inner")));
+    assert!(replies[5].contains(&label("outer")));
+
+    // the stale hover still points into the text it was worked out from
+    assert!(replies[6].contains(r#""message":"#));
+    assert_eq!(replies[7], replies[3].replace(r#""id":3"#, r#""id":6"#));
+}
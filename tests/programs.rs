@@ -0,0 +1,222 @@
+//! Golden tests: runs each program in `tests/programs` through the `dwislpy` binary, just as
+//! from the command line, and compares what it does with what it is expected to do.
+//!
+//! For `name.dwislpy`, the optional files beside it are:
+//!
+//! - `name.args`, arguments to give `dwislpy` before the file name, separated by whitespace,
+//!   like `test` or `--max-steps 100` (otherwise there are none)
+//! - `name.in`, given to the program as its stdin (otherwise the stdin is empty)
+//! - `name.out`, what it should print to stdout (otherwise nothing)
+//! - `name.err`, if it should fail: a first line `exit status: N`, then what it should print to
+//!   stderr. Without one, the program should succeed and print nothing to stderr.
+//!
+//! The programs in `tests/fmt` are formatted instead, each in a copy of its own: `name.out` is
+//! what formatting makes of it, which formatting again has to leave alone. `fmt --check` has to
+//! fail on a program that formatting changes and pass on one it doesn't.
+//!
+//! Run with `BLESS=1` to rewrite the expectations from what the programs actually do.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// What running a program did, in the form it is expected in.
+struct Outcome {
+    out: String,
+    err: Option<String>,
+}
+
+/// Run `dwislpy` on a file in a directory, with the given arguments before its name and input
+/// on its stdin, and tell whether it succeeded.
+fn dwislpy(dir: &Path, args: &[&str], file: &Path, input: &[u8]) -> Outcome {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dwislpy"))
+        // so that tracebacks name the file the same way wherever the repo is
+        .current_dir(dir)
+        .args(args)
+        .arg(file.file_name().expect("programs are files"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Should have been able to start dwislpy");
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // a program may stop before reading all of its input
+    let _ = stdin.write_all(input);
+    drop(stdin);
+    let output = child
+        .wait_with_output()
+        .expect("Should have been able to run dwislpy");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let err = match output.status.code() {
+        Some(0) if stderr.is_empty() => None,
+        Some(code) => Some(format!("exit status: {code}\n{stderr}")),
+        None => Some(format!("exit status: killed\n{stderr}")),
+    };
+    Outcome {
+        out: String::from_utf8_lossy(&output.stdout).into_owned(),
+        err,
+    }
+}
+
+/// Run a program with the arguments and input beside it.
+fn run(dir: &Path, program: &Path) -> Outcome {
+    let args = fs::read_to_string(program.with_extension("args")).unwrap_or_default();
+    let input = fs::read(program.with_extension("in")).unwrap_or_default();
+    let args: Vec<_> = args.split_whitespace().collect();
+    dwislpy(dir, &args, program, &input)
+}
+
+/// Read an expectation, where a missing file means nothing is expected.
+fn expected(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+/// Write an expectation, removing the file when nothing is expected.
+fn bless(path: &Path, actual: Option<&str>) {
+    match actual {
+        Some(actual) => fs::write(path, actual).expect("Should have been able to bless"),
+        None if path.exists() => fs::remove_file(path).expect("Should have been able to bless"),
+        None => {}
+    }
+}
+
+fn compare(path: &Path, expected: Option<&str>, actual: Option<&str>, failures: &mut Vec<String>) {
+    if expected != actual {
+        failures.push(format!(
+            "{}\n--- expected\n{}--- actual\n{}",
+            path.display(),
+            expected.unwrap_or("(nothing)\n"),
+            actual.unwrap_or("(nothing)\n"),
+        ));
+    }
+}
+
+#[test]
+fn programs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let blessing = env::var_os("BLESS").is_some_and(|bless| !bless.is_empty());
+    let mut programs: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("Should have been able to list the programs")
+        .map(|entry| {
+            entry
+                .expect("Should have been able to list the programs")
+                .path()
+        })
+        .filter(|path| path.extension().is_some_and(|ext| ext == "dwislpy"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "no programs in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for program in &programs {
+        let Outcome { out, err } = run(&dir, program);
+        let (out_path, err_path) = (program.with_extension("out"), program.with_extension("err"));
+        let out = Some(out).filter(|out| !out.is_empty());
+        if blessing {
+            bless(&out_path, out.as_deref());
+            bless(&err_path, err.as_deref());
+        } else {
+            compare(
+                &out_path,
+                expected(&out_path).as_deref(),
+                out.as_deref(),
+                &mut failures,
+            );
+            compare(
+                &err_path,
+                expected(&err_path).as_deref(),
+                err.as_deref(),
+                &mut failures,
+            );
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} expectations failed (rerun with BLESS=1 to accept the changes):\n\n{}",
+        failures.len(),
+        2 * programs.len(),
+        failures.join("\n"),
+    );
+}
+
+#[test]
+fn formatting() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fmt");
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fmt");
+    fs::create_dir_all(&scratch).expect("Should have been able to make a scratch directory");
+    let blessing = env::var_os("BLESS").is_some_and(|bless| !bless.is_empty());
+    let mut programs: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("Should have been able to list the programs")
+        .map(|entry| {
+            entry
+                .expect("Should have been able to list the programs")
+                .path()
+        })
+        .filter(|path| path.extension().is_some_and(|ext| ext == "dwislpy"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "no programs in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for program in &programs {
+        let source = fs::read_to_string(program).expect("Should have been able to read");
+        let copy = scratch.join(program.file_name().expect("programs are files"));
+        fs::write(&copy, &source).expect("Should have been able to copy the program");
+
+        let checked = dwislpy(&scratch, &["fmt", "--check"], &copy, &[]);
+        let formatted = dwislpy(&scratch, &["fmt"], &copy, &[]);
+        let out = fs::read_to_string(&copy).expect("Should have been able to read the copy");
+        let out_path = program.with_extension("out");
+        if blessing {
+            bless(&out_path, Some(&out));
+        } else {
+            compare(
+                &out_path,
+                expected(&out_path).as_deref(),
+                Some(&out),
+                &mut failures,
+            );
+        }
+
+        // checking fails exactly when formatting changes something
+        let expected_check = (out != source).then(|| {
+            let name = copy
+                .file_name()
+                .expect("programs are files")
+                .to_string_lossy();
+            format!("exit status: 1\n{name} is not formatted\nError: \"formatting check failed\"\n")
+        });
+        let name = format!("{} (fmt --check)", program.display());
+        compare(
+            Path::new(&name),
+            expected_check.as_deref(),
+            checked.err.as_deref(),
+            &mut failures,
+        );
+        let name = format!("{} (fmt)", program.display());
+        compare(
+            Path::new(&name),
+            None,
+            formatted.err.as_deref(),
+            &mut failures,
+        );
+
+        // and formatting what it formatted changes nothing
+        let again = dwislpy(&scratch, &["fmt", "--check"], &copy, &[]);
+        let name = format!("{} (fmt --check after fmt)", program.display());
+        compare(Path::new(&name), None, again.err.as_deref(), &mut failures);
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} programs failed to format (rerun with BLESS=1 to accept the changes):\n\n{}",
+        failures.len(),
+        programs.len(),
+        failures.join("\n"),
+    );
+}